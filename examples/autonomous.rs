@@ -0,0 +1,120 @@
+//! An example of an autonomous routine composed from smaller commands, rather
+//! than hand-rolled with `tokio::select!`.
+
+use std::time::Duration;
+
+use mekena::{command::wait, prelude::*};
+
+#[main(signals)]
+async fn main(system: System) -> Result<(), miette::Error> {
+    system
+        .add_node(CommandScheduler::new())
+        .add_node(Drivetrain)
+        .add_node(Shooter)
+        .add_node(Autonomous)
+        .start()
+        .await?;
+
+    Ok(())
+}
+
+struct Drivetrain;
+
+#[node]
+impl Node for Drivetrain {}
+
+struct Shooter;
+
+#[node]
+impl Node for Shooter {}
+
+struct Autonomous;
+
+#[node]
+impl Node for Autonomous {
+    async fn running(&mut self, ctx: &Context) {
+        // Drive out while spinning up the shooter, shoot (giving up after a
+        // second), wait a moment, then drive back.
+        let routine = Drive::metres(10)
+            .deadline_with(SpinUp)
+            .and_then(Shoot { shots: 0 }.with_timeout(Duration::from_secs(1)))
+            .and_then(wait(Duration::from_millis(500)))
+            .and_then(Drive::metres(10));
+
+        let id = ctx.schedule(routine).unwrap();
+
+        while ctx.is_scheduled(id) {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        println!("Autonomous done.");
+        ctx.shutdown().await;
+    }
+}
+
+struct Drive {
+    target: u32,
+    driven: u32,
+}
+
+impl Drive {
+    fn metres(target: u32) -> Self {
+        Self { target, driven: 0 }
+    }
+}
+
+#[command]
+impl Command for Drive {
+    fn requirements(&self) -> Vec<Resource> {
+        vec![Resource::of::<Drivetrain>()]
+    }
+
+    async fn initialize(&mut self, _ctx: &Context) {
+        self.driven = 0;
+        println!("Driving {}m...", self.target);
+    }
+
+    async fn execute(&mut self, _ctx: &Context) {
+        self.driven += 1;
+    }
+
+    fn is_finished(&mut self, _ctx: &Context) -> bool {
+        self.driven >= self.target
+    }
+}
+
+struct SpinUp;
+
+#[command]
+impl Command for SpinUp {
+    fn requirements(&self) -> Vec<Resource> {
+        vec![Resource::of::<Shooter>()]
+    }
+
+    async fn initialize(&mut self, _ctx: &Context) {
+        println!("Spinning up...");
+    }
+
+    async fn end(&mut self, _ctx: &Context, interrupted: bool) {
+        println!("Spun up (interrupted: {interrupted})");
+    }
+}
+
+struct Shoot {
+    shots: u32,
+}
+
+#[command]
+impl Command for Shoot {
+    fn requirements(&self) -> Vec<Resource> {
+        vec![Resource::of::<Shooter>()]
+    }
+
+    async fn execute(&mut self, _ctx: &Context) {
+        self.shots += 1;
+    }
+
+    async fn end(&mut self, _ctx: &Context, interrupted: bool) {
+        println!("Took {} shots (interrupted: {interrupted})", self.shots);
+    }
+}
@@ -0,0 +1,77 @@
+//! An example of a behavior tree loaded from JSON: drive to a game piece, pick
+//! it up (trying a few times), then shoot it, all within a time limit.
+
+use std::time::Duration;
+
+use mekena::behavior::Description;
+use mekena::prelude::*;
+
+const ROUTINE: &str = r#"
+{
+    "timeout": {
+        "millis": 2000,
+        "child": {
+            "sequence": [
+                { "leaf": "drive to piece" },
+                { "retry": { "attempts": 3, "child": { "leaf": "pick up" } } },
+                { "selector": [
+                    { "leaf": "has piece" },
+                    { "leaf": "give up" }
+                ] },
+                { "leaf": "shoot" }
+            ]
+        }
+    }
+}
+"#;
+
+state_key!(ATTEMPTS: u32 = "attempts");
+
+#[main(signals)]
+async fn main(system: System) -> Result<(), miette::Error> {
+    let leaves = Leaves::new()
+        .action("drive to piece", |_ctx| async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            println!("Arrived at the piece");
+            true
+        })
+        .action("pick up", |ctx| async move {
+            let attempt = ctx.get(&ATTEMPTS).unwrap().map_or(0, |attempts| *attempts) + 1;
+            ctx.insert(&ATTEMPTS, attempt).unwrap();
+
+            // Only the second attempt gets a grip.
+            println!("Picking up, attempt {attempt}");
+            attempt == 2
+        })
+        .condition("has piece", |ctx| matches!(ctx.get(&ATTEMPTS), Ok(Some(_))))
+        .action("give up", |_ctx| async { false })
+        .action("shoot", |ctx| async move {
+            println!("Shooting");
+            ctx.send(Shot).await.is_ok()
+        });
+
+    let description: Description = serde_json::from_str(ROUTINE).unwrap();
+    let root = Behavior::load(&description, &leaves)?;
+
+    system
+        .add_node(Autonomous(BehaviorTree::new(root)))
+        .start()
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+struct Shot;
+
+/// Runs the routine, printing the trace of its last tick.
+struct Autonomous(BehaviorTree);
+
+#[node]
+impl Node for Autonomous {
+    async fn running(&mut self, ctx: &Context) {
+        self.0.running(ctx).await;
+        print!("{}", self.0.trace());
+        ctx.shutdown().await;
+    }
+}
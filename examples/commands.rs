@@ -0,0 +1,134 @@
+//! An example of the command scheduler: commands claim the nodes they drive,
+//! and a new command either interrupts the one holding its resources, or is
+//! rejected.
+
+use std::time::Duration;
+
+use mekena::prelude::*;
+
+#[main(signals)]
+async fn main(system: System) -> Result<(), miette::Error> {
+    system
+        .add_node(CommandScheduler::new())
+        .add_node(Drivetrain)
+        .add_node(Shooter)
+        .add_node(Robot)
+        .start()
+        .await?;
+
+    Ok(())
+}
+
+/// Owns the drive motors. Commands requiring it may drive them.
+struct Drivetrain;
+
+#[node]
+impl Node for Drivetrain {}
+
+/// Owns the shooter motors.
+struct Shooter;
+
+#[node]
+impl Node for Shooter {}
+
+/// Schedules commands, as joystick buttons would.
+struct Robot;
+
+#[node]
+impl Node for Robot {
+    async fn running(&mut self, ctx: &Context) {
+        ctx.schedule(Drive {
+            metres: 0,
+            target: 50,
+        })
+        .unwrap();
+        ctx.schedule(Shoot { balls: 0 }).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // Needs the drivetrain, so the drive command is interrupted.
+        ctx.schedule(Turn { degrees: 0 }).unwrap();
+
+        // The shooter won't give up its resource halfway through shooting.
+        if let Err(e) = ctx.schedule(Shoot { balls: 0 }) {
+            println!("{e}");
+        }
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        ctx.shutdown().await;
+    }
+}
+
+struct Drive {
+    metres: u32,
+    target: u32,
+}
+
+#[command]
+impl Command for Drive {
+    fn requirements(&self) -> Vec<Resource> {
+        vec![Resource::of::<Drivetrain>()]
+    }
+
+    async fn execute(&mut self, _ctx: &Context) {
+        self.metres += 1;
+    }
+
+    fn is_finished(&mut self, _ctx: &Context) -> bool {
+        self.metres == self.target
+    }
+
+    async fn end(&mut self, _ctx: &Context, interrupted: bool) {
+        println!("Drove {}m (interrupted: {interrupted})", self.metres);
+    }
+}
+
+struct Turn {
+    degrees: u32,
+}
+
+#[command]
+impl Command for Turn {
+    fn requirements(&self) -> Vec<Resource> {
+        vec![Resource::of::<Drivetrain>()]
+    }
+
+    async fn execute(&mut self, _ctx: &Context) {
+        self.degrees += 15;
+    }
+
+    fn is_finished(&mut self, _ctx: &Context) -> bool {
+        self.degrees >= 90
+    }
+
+    async fn end(&mut self, _ctx: &Context, interrupted: bool) {
+        println!("Turned {}° (interrupted: {interrupted})", self.degrees);
+    }
+}
+
+struct Shoot {
+    balls: u32,
+}
+
+#[command]
+impl Command for Shoot {
+    fn requirements(&self) -> Vec<Resource> {
+        vec![Resource::of::<Shooter>()]
+    }
+
+    fn interruption(&self) -> InterruptionBehavior {
+        InterruptionBehavior::CancelIncoming
+    }
+
+    async fn execute(&mut self, _ctx: &Context) {
+        self.balls += 1;
+    }
+
+    fn is_finished(&mut self, _ctx: &Context) -> bool {
+        self.balls == 20
+    }
+
+    async fn end(&mut self, _ctx: &Context, interrupted: bool) {
+        println!("Shot {} balls (interrupted: {interrupted})", self.balls);
+    }
+}
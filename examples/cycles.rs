@@ -0,0 +1,44 @@
+//! An example of running the same system several times, e.g. once for the
+//! autonomous period of a match and once for the teleoperated period.
+
+use std::time::Duration;
+
+use mekena::prelude::*;
+
+#[main]
+async fn main(system: System) -> Result<(), miette::Error> {
+    let mut system = system.add_node(Drivetrain { distance: 0 });
+
+    for _ in 0..2 {
+        let reason = system.start().await?;
+        println!("Cycle ended ({reason}), system is {:?}", system.get_state());
+    }
+
+    Ok(())
+}
+
+struct Drivetrain {
+    /// Kept across cycles, since the node isn't rebuilt.
+    distance: u32,
+}
+
+#[node]
+impl Node for Drivetrain {
+    async fn starting(&mut self, ctx: &Context) {
+        println!("Starting cycle {}", ctx.cycle());
+    }
+
+    async fn running(&mut self, ctx: &Context) {
+        for _ in 0..3 {
+            self.distance += 1;
+            println!("Driven {} metres", self.distance);
+            tokio::time::sleep(Duration::from_millis(300)).await;
+        }
+
+        ctx.end_cycle();
+    }
+
+    async fn stopping(&mut self, ctx: &Context) {
+        println!("Stopping cycle {}", ctx.cycle());
+    }
+}
@@ -0,0 +1,95 @@
+//! An example of default commands: the arm holds its position whenever no
+//! other command is moving it.
+
+use std::time::Duration;
+
+use mekena::prelude::*;
+
+#[main(signals)]
+async fn main(system: System) -> Result<(), miette::Error> {
+    system
+        .add_node(CommandScheduler::new())
+        .add_node(Arm)
+        .add_node(Robot)
+        .start()
+        .await?;
+
+    Ok(())
+}
+
+/// Owns the arm motor.
+struct Arm;
+
+#[node]
+impl Node for Arm {
+    async fn starting(&mut self, ctx: &Context) {
+        ctx.set_default_command(Resource::of::<Arm>(), || Hold)
+            .unwrap();
+    }
+}
+
+/// Moves the arm now and then.
+struct Robot;
+
+#[node]
+impl Node for Robot {
+    async fn running(&mut self, ctx: &Context) {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        println!("Arm held by {:?}", ctx.holder(&Resource::of::<Arm>()));
+
+        ctx.schedule(Raise { steps: 5 }).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        println!("Arm held by {:?}", ctx.holder(&Resource::of::<Arm>()));
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        println!("Arm held by {:?}", ctx.holder(&Resource::of::<Arm>()));
+
+        ctx.shutdown().await;
+    }
+}
+
+/// Holds the arm where it is.
+struct Hold;
+
+#[command]
+impl Command for Hold {
+    fn requirements(&self) -> Vec<Resource> {
+        vec![Resource::of::<Arm>()]
+    }
+
+    async fn initialize(&mut self, _ctx: &Context) {
+        println!("Holding the arm");
+    }
+
+    async fn end(&mut self, _ctx: &Context, interrupted: bool) {
+        println!("Stopped holding the arm (interrupted: {interrupted})");
+    }
+}
+
+/// Raises the arm a few steps.
+struct Raise {
+    steps: u32,
+}
+
+#[command]
+impl Command for Raise {
+    fn requirements(&self) -> Vec<Resource> {
+        vec![Resource::of::<Arm>()]
+    }
+
+    async fn initialize(&mut self, _ctx: &Context) {
+        println!("Raising the arm");
+    }
+
+    async fn execute(&mut self, _ctx: &Context) {
+        self.steps -= 1;
+    }
+
+    fn is_finished(&mut self, _ctx: &Context) -> bool {
+        self.steps == 0
+    }
+
+    async fn end(&mut self, _ctx: &Context, _interrupted: bool) {
+        println!("Arm raised");
+    }
+}
@@ -0,0 +1,57 @@
+//! An example of ordering node startup. The odometry node needs the IMU to be
+//! initialized first, so it declares a dependency on it.
+
+use mekena::prelude::*;
+
+#[main]
+async fn main(system: System) -> Result<(), miette::Error> {
+    system
+        .add_node_with(Odometry, NodeConfig::new().depends_on::<Imu>())
+        .add_node_with(Imu, NodeConfig::new().name("imu"))
+        .add_node_with(Logger, NodeConfig::new().depends_on_named("imu"))
+        .start()
+        .await?;
+
+    Ok(())
+}
+
+struct Imu;
+
+#[node]
+impl Node for Imu {
+    async fn starting(&mut self, _ctx: &Context) {
+        println!("IMU initializing...");
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        println!("IMU initialized.");
+    }
+
+    async fn stopping(&mut self, _ctx: &Context) {
+        println!("IMU stopping, after everything that depends on it.");
+    }
+}
+
+struct Odometry;
+
+#[node]
+impl Node for Odometry {
+    async fn starting(&mut self, _ctx: &Context) {
+        println!("Odometry starting, now that the IMU is ready.");
+    }
+
+    async fn stopping(&mut self, _ctx: &Context) {
+        println!("Odometry stopping.");
+    }
+}
+
+struct Logger;
+
+#[node]
+impl Node for Logger {
+    async fn starting(&mut self, _ctx: &Context) {
+        println!("Logger starting, alongside odometry.");
+    }
+
+    async fn stopping(&mut self, _ctx: &Context) {
+        println!("Logger stopping.");
+    }
+}
@@ -0,0 +1,53 @@
+//! An example of observing lifecycle events, e.g. to drive a dashboard. The
+//! handle is taken before the system starts, and watched from another task.
+
+use mekena::prelude::*;
+
+#[main]
+async fn main(system: System) -> Result<(), miette::Error> {
+    let mut system = system.add_node(Worker).add_node(Watcher);
+    let handle = system.handle();
+
+    tokio::spawn(async move {
+        let mut events = handle.events();
+
+        while let Some(event) = events.next().await {
+            println!("[dashboard] {event:?} (system is {:?})", handle.state());
+        }
+    });
+
+    system.start().await?;
+
+    Ok(())
+}
+
+struct Worker;
+
+#[node]
+impl Node for Worker {
+    async fn running(&mut self, ctx: &Context) {
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        ctx.shutdown().await;
+    }
+}
+
+/// Nodes can watch events too, through their context.
+struct Watcher;
+
+#[node]
+impl Node for Watcher {
+    async fn running(&mut self, ctx: &Context) {
+        let mut events = ctx.events();
+
+        while let Some(event) = events.next().await {
+            if let LifecycleEvent::Node {
+                name,
+                event: NodeEvent::Running,
+                ..
+            } = event
+            {
+                println!("[watcher] {name} is running");
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! An example of a state machine: an intake that picks up a game piece, holds
+//! it, and only shoots once the shooter is up to speed.
+
+use std::time::Duration;
+
+use mekena::prelude::*;
+
+#[main(signals)]
+async fn main(system: System) -> Result<(), miette::Error> {
+    system
+        .add_node(Intake::new())
+        .add_node(Driver)
+        .start()
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IntakeState {
+    Idle,
+    Intaking,
+    Holding,
+    Shooting,
+}
+
+#[derive(Debug)]
+struct Intake(StateMachine<IntakeState>);
+
+/// The driver pressed the intake button.
+#[derive(Clone, Debug)]
+struct IntakePressed;
+
+/// The beam break sensor saw a game piece.
+#[derive(Clone, Debug)]
+struct PieceDetected;
+
+/// The driver pressed the shoot button, with the shooter at some speed.
+#[derive(Clone, Debug)]
+struct ShootPressed {
+    rpm: u32,
+}
+
+/// The game piece left the robot.
+#[derive(Clone, Debug)]
+struct PieceGone;
+
+/// Tells the feeder to push the game piece into the shooter.
+#[derive(Debug)]
+struct Feed;
+
+impl Intake {
+    fn new() -> Self {
+        use IntakeState::*;
+
+        Self(
+            StateMachine::new(Idle)
+                .transition::<IntakePressed>(Idle, Intaking)
+                .transition::<PieceDetected>(Intaking, Holding)
+                .guarded::<ShootPressed>(Holding, Shooting, |shoot| shoot.rpm >= 3000)
+                .transition::<PieceGone>(Shooting, Idle)
+                .on_enter(Intaking, |_| async { println!("Rollers on") })
+                .on_exit(Intaking, |_| async { println!("Rollers off") })
+                .on_enter(Shooting, |ctx| async move {
+                    println!("Feeding the shooter");
+                    ctx.send(Feed).await.unwrap();
+                }),
+        )
+    }
+}
+
+#[node]
+impl Node for Intake {
+    async fn running(&mut self, ctx: &Context) {
+        loop {
+            match self.0.step(ctx).await {
+                Ok(Some(record)) => println!("{:?} -> {:?}", record.from, record.to),
+                Ok(None) => println!("Ignored a message in {:?}", self.0.state()),
+                Err(_) => return,
+            }
+        }
+    }
+
+    async fn stopping(&mut self, _ctx: &Context) {
+        println!("{}", self.0.to_dot());
+    }
+}
+
+/// Presses buttons, and plays the part of the sensors.
+struct Driver;
+
+#[node]
+impl Node for Driver {
+    async fn running(&mut self, ctx: &Context) {
+        let pause = || tokio::time::sleep(Duration::from_millis(100));
+
+        // Give the intake time to start watching for messages.
+        pause().await;
+        ctx.send(IntakePressed).await.unwrap();
+        pause().await;
+        ctx.send(PieceDetected).await.unwrap();
+        pause().await;
+        // Too early: the shooter isn't up to speed.
+        ctx.send(ShootPressed { rpm: 1200 }).await.unwrap();
+        pause().await;
+        ctx.send(ShootPressed { rpm: 3500 }).await.unwrap();
+        pause().await;
+        ctx.send(PieceGone).await.unwrap();
+        pause().await;
+
+        ctx.shutdown().await;
+    }
+}
@@ -0,0 +1,49 @@
+//! An example of controlling a system from outside of it, as a GUI or a test
+//! harness embedding mekena would.
+
+use std::time::Duration;
+
+use mekena::prelude::*;
+
+#[main]
+async fn main(system: System) -> Result<(), miette::Error> {
+    let mut system = system.add_node(Printer);
+    let handle = system.handle();
+
+    let controller = tokio::spawn({
+        let handle = handle.clone();
+
+        async move {
+            for i in 0..3 {
+                handle.send(Command(i)).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            }
+
+            for node in handle.nodes() {
+                println!("{} ({}) ready: {}", node.name(), node.id(), node.is_ready());
+            }
+
+            handle.shutdown();
+            println!("Stopped because: {}", handle.join().await);
+        }
+    });
+
+    system.start().await?;
+    controller.await.unwrap();
+
+    Ok(())
+}
+
+struct Command(i32);
+
+struct Printer;
+
+#[node]
+impl Node for Printer {
+    async fn running(&mut self, ctx: &Context) {
+        loop {
+            let command = ctx.recv::<Command>().await.unwrap();
+            println!("Received command {}", command.0);
+        }
+    }
+}
@@ -5,18 +5,27 @@ use mekena::prelude::*;
 ///
 /// We unwrap to a `miette` error here, but of course, you can unwrap in any way
 /// you choose.
-#[main]
+///
+/// With `signals`, pressing Ctrl-C shuts the system down gracefully, so every
+/// node's `stopping` hook still runs. Pressing it again exits immediately.
+#[main(signals)]
 async fn main(system: System) -> Result<(), miette::Error> {
     system
         .add_node(SomeNode1)
         .add_node(SomeNode2::default())
-        //     .add_state(SomeState)
+        .add_state(SomeState { limit: 10 })
         .start()
         .await?;
 
     Ok(())
 }
 
+/// State shared by every node. There is only ever one `SomeState`, which nodes
+/// get with `ctx.resource::<SomeState>()`.
+struct SomeState {
+    limit: i32,
+}
+
 /// The structure of `SomeNode1`. Notice that this node does not keep any state.
 struct SomeNode1;
 
@@ -53,11 +62,13 @@ impl Node for SomeNode2 {
         println!("SomeNode2 starting...");
     }
 
-    /// This will run until the counter reaches 10. Then, it will stop the
-    /// *whole* context.
+    /// This will run until the counter reaches the limit in `SomeState`. Then,
+    /// it will stop the *whole* context.
     async fn running(&mut self, ctx: &Context) {
+        let limit = ctx.resource::<SomeState>().unwrap().limit;
+
         loop {
-            if self.counter == 10 {
+            if self.counter == limit {
                 ctx.shutdown().await;
             } else {
                 println!("SomeNode2 running...");
@@ -0,0 +1,61 @@
+//! An example of wall-clock jobs: a message every second, and a log rotation
+//! every minute that catches up on the runs missed while the program was down.
+
+use std::time::{Duration, SystemTime};
+
+use mekena::prelude::*;
+
+#[main(signals)]
+async fn main(system: System) -> Result<(), miette::Error> {
+    system
+        .add_node(JobScheduler::new().resolution(Duration::from_millis(100))?)
+        .add_node(Monitor)
+        .start()
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+struct Sample;
+
+struct Monitor;
+
+#[node]
+impl Node for Monitor {
+    async fn starting(&mut self, ctx: &Context) {
+        ctx.add_job(
+            Job::interval("sample", Duration::from_secs(1))
+                .unwrap()
+                .send(|| Sample),
+        )
+        .unwrap();
+
+        // Pretend the program last rotated the logs five minutes ago.
+        let last_run = SystemTime::now() - Duration::from_secs(5 * 60);
+        ctx.add_job(
+            Job::cron("rotate logs", "* * * * *")
+                .unwrap()
+                .call(|_| println!("Rotating logs"))
+                .missed_runs(MissedRuns::RunAll)
+                .last_run(last_run),
+        )
+        .unwrap();
+
+        for job in ctx.jobs() {
+            println!("{} runs {}", job.name, job.every);
+        }
+    }
+
+    async fn running(&mut self, ctx: &Context) {
+        for _ in 0..3 {
+            ctx.recv::<Sample>().await.unwrap();
+            println!("Sampling the field monitor");
+        }
+
+        for job in ctx.jobs() {
+            println!("{} has run {} times", job.name, job.runs);
+        }
+        ctx.shutdown().await;
+    }
+}
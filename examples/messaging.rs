@@ -24,7 +24,7 @@ impl Node for SomeNode1 {
     async fn running(&mut self, ctx: &Context) {
         loop {
             let m: Box<MyMessage> = ctx.recv().await.unwrap();
-            println!("Received a message: {m:?}");
+            println!("Received a message: {}", m.0);
         }
     }
 }
@@ -0,0 +1,51 @@
+//! An example of robot-style operating modes, with a field controller moving
+//! the robot from autonomous to teleop, as a driver station would.
+
+use std::time::Duration;
+
+use mekena::prelude::*;
+
+#[main(signals)]
+async fn main(system: System) -> Result<(), miette::Error> {
+    system
+        .modes(ModeConfig::new().period(Duration::from_millis(250)))
+        .add_node(FieldControl)
+        .add_node(Drivetrain)
+        .start()
+        .await?;
+
+    Ok(())
+}
+
+struct FieldControl;
+
+#[node]
+impl Node for FieldControl {
+    async fn running(&mut self, ctx: &Context) {
+        for mode in [Mode::AUTONOMOUS, Mode::TELEOP, Mode::DISABLED] {
+            ctx.request_mode(mode);
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+
+        ctx.shutdown().await;
+    }
+}
+
+struct Drivetrain;
+
+#[node]
+impl Node for Drivetrain {
+    async fn mode_enter(&mut self, _ctx: &Context, mode: Mode) {
+        println!("Drivetrain entering {mode}");
+    }
+
+    async fn mode_periodic(&mut self, _ctx: &Context, mode: Mode) {
+        if mode != Mode::DISABLED {
+            println!("Drivetrain driving in {mode}...");
+        }
+    }
+
+    async fn mode_exit(&mut self, _ctx: &Context, mode: Mode) {
+        println!("Drivetrain leaving {mode}");
+    }
+}
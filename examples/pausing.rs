@@ -0,0 +1,53 @@
+//! An example of pausing a node and resuming it later, e.g. a shooter that is
+//! disabled while the robot climbs.
+
+use std::time::Duration;
+
+use mekena::prelude::*;
+
+#[main]
+async fn main(system: System) -> Result<(), miette::Error> {
+    system.add_node(Climber).start().await?;
+
+    Ok(())
+}
+
+struct Climber;
+
+#[node]
+impl Node for Climber {
+    async fn running(&mut self, ctx: &Context) {
+        let shooter = ctx.spawn_node(Shooter);
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        println!("Climbing, shooter is {:?}", shooter.state());
+        shooter.pause();
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        println!("Done climbing, shooter is {:?}", shooter.state());
+        shooter.resume();
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        ctx.shutdown().await;
+    }
+}
+
+struct Shooter;
+
+#[node]
+impl Node for Shooter {
+    async fn running(&mut self, _ctx: &Context) {
+        loop {
+            println!("Shooting...");
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    async fn pause(&mut self, _ctx: &Context) {
+        println!("Shooter disabled.");
+    }
+
+    async fn resume(&mut self, _ctx: &Context) {
+        println!("Shooter enabled.");
+    }
+}
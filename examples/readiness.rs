@@ -0,0 +1,65 @@
+//! An example of readiness gating. The sensor node has started once its
+//! `starting` hook returns, but it isn't ready until it has calibrated. The
+//! system only enters its running phase once every node is ready.
+
+use std::time::Duration;
+
+use mekena::prelude::*;
+
+#[main]
+async fn main(system: System) -> Result<(), miette::Error> {
+    system
+        .add_node_with(
+            Sensor { samples: 0 },
+            NodeConfig::new()
+                .manual_ready()
+                .startup_timeout(Duration::from_secs(5)),
+        )
+        .add_node(Controller)
+        .start()
+        .await?;
+
+    Ok(())
+}
+
+struct Sensor {
+    samples: u32,
+}
+
+#[node]
+impl Node for Sensor {
+    async fn starting(&mut self, _ctx: &Context) {
+        println!("Sensor powered on, calibrating...");
+    }
+
+    /// Since the sensor is registered with `manual_ready`, this runs as soon as
+    /// `starting` returns, while the rest of the system waits for us.
+    async fn running(&mut self, ctx: &Context) {
+        loop {
+            self.samples += 1;
+
+            if self.samples == 3 && !ctx.is_ready() {
+                println!("Sensor calibrated.");
+                ctx.ready();
+            }
+
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+}
+
+struct Controller;
+
+#[node]
+impl Node for Controller {
+    async fn starting(&mut self, _ctx: &Context) {
+        println!("Controller started.");
+    }
+
+    async fn running(&mut self, ctx: &Context) {
+        println!("Controller running, the sensor is ready.");
+        ctx.wait_ready::<Sensor>().await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        ctx.shutdown().await;
+    }
+}
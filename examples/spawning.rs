@@ -0,0 +1,53 @@
+//! An example of spawning nodes at runtime, e.g. one per camera that shows up
+//! after the system has started.
+
+use mekena::prelude::*;
+
+#[main]
+async fn main(system: System) -> Result<(), miette::Error> {
+    system.add_node(CameraManager).start().await?;
+
+    Ok(())
+}
+
+struct CameraManager;
+
+#[node]
+impl Node for CameraManager {
+    async fn running(&mut self, ctx: &Context) {
+        // Pretend we detected two cameras.
+        let cameras: Vec<NodeHandle> = (0..2).map(|id| ctx.spawn_node(Camera { id })).collect();
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+        // Camera 0 was unplugged.
+        cameras[0].stop();
+        cameras[0].join().await;
+        println!("Camera 0 torn down.");
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+        ctx.shutdown().await;
+    }
+}
+
+struct Camera {
+    id: u32,
+}
+
+#[node]
+impl Node for Camera {
+    async fn starting(&mut self, _ctx: &Context) {
+        println!("Camera {} starting...", self.id);
+    }
+
+    async fn running(&mut self, _ctx: &Context) {
+        loop {
+            println!("Camera {} capturing...", self.id);
+            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn stopping(&mut self, _ctx: &Context) {
+        println!("Camera {} stopping...", self.id);
+    }
+}
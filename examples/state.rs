@@ -0,0 +1,38 @@
+//! An example of typed state: values are read back as the type they were
+//! stored as, and using another type is an error rather than a panic.
+
+use mekena::prelude::*;
+
+state_key!(DISTANCE: f64 = "distance");
+
+#[main]
+async fn main(system: System) -> Result<(), miette::Error> {
+    system.add_node(Odometry).start().await?;
+
+    Ok(())
+}
+
+struct Odometry;
+
+#[node]
+impl Node for Odometry {
+    async fn running(&mut self, ctx: &Context) {
+        ctx.insert(&DISTANCE, 0.0).unwrap();
+
+        for _ in 0..3 {
+            *ctx.get_mut(&DISTANCE).unwrap().unwrap() += 1.5;
+        }
+
+        let distance = ctx.get(&DISTANCE).unwrap().unwrap();
+        println!("Travelled {} metres", *distance);
+        drop(distance);
+
+        // Keys can also be strings, which aren't checked until runtime. The
+        // distance is a float, so this is refused.
+        if let Err(e) = ctx.insert("distance", "far") {
+            println!("{e}");
+        }
+
+        ctx.shutdown().await;
+    }
+}
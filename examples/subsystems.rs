@@ -0,0 +1,67 @@
+//! An example of grouping nodes into a subsystem, e.g. a drivetrain made of a
+//! motor and an encoder, which talk over their own mailbox.
+
+use std::time::Duration;
+
+use mekena::prelude::*;
+
+#[main(signals)]
+async fn main(system: System) -> Result<(), miette::Error> {
+    let drivetrain = Subsystem::new().add_node(Encoder).add_node(Motor);
+
+    system
+        .add_node_with(drivetrain, NodeConfig::new().name("drivetrain"))
+        .add_node(Dashboard)
+        .start()
+        .await?;
+
+    Ok(())
+}
+
+struct Ticks(u32);
+
+struct Speed(u32);
+
+struct Encoder;
+
+#[node]
+impl Node for Encoder {
+    async fn running(&mut self, ctx: &Context) {
+        for ticks in 0..5 {
+            ctx.send(Ticks(ticks * 10)).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+
+    async fn stopping(&mut self, _ctx: &Context) {
+        println!("Encoder stopping...");
+    }
+}
+
+struct Motor;
+
+#[node]
+impl Node for Motor {
+    async fn running(&mut self, ctx: &Context) {
+        for _ in 0..5 {
+            let ticks = ctx.recv::<Ticks>().await.unwrap();
+            ctx.send_outer(Speed(ticks.0 / 2)).await.unwrap();
+        }
+    }
+
+    async fn stopping(&mut self, _ctx: &Context) {
+        println!("Motor stopping...");
+    }
+}
+
+struct Dashboard;
+
+#[node]
+impl Node for Dashboard {
+    async fn running(&mut self, ctx: &Context) {
+        for _ in 0..5 {
+            let speed = ctx.recv::<Speed>().await.unwrap();
+            println!("Drivetrain speed: {}", speed.0);
+        }
+    }
+}
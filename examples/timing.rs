@@ -1,4 +1,6 @@
-//! Let's experiment with how accurate the timing is of `mekena-messaging`.
+//! Let's experiment with how accurate the timing is of periodic nodes.
+
+use std::time::Duration;
 
 use lazy_static::lazy_static;
 use mekena::prelude::*;
@@ -8,33 +10,45 @@ lazy_static! {
     static ref START_TIME: Instant = tokio::time::Instant::now();
 }
 
-#[main]
+#[main(signals)]
 async fn main(system: System) -> Result<(), miette::Error> {
+    let every_second = || NodeConfig::new().period(Duration::from_secs(1));
+
     system
-        .add_node(SomeNode1)
-        .add_node(SomeNode1)
-        .add_node(SomeNode1)
+        .add_node_with(SomeNode1, every_second())
+        .add_node_with(SomeNode1, every_second())
+        .add_node_with(SomeNode1, every_second().missed_ticks(MissedTicks::Burst))
         .start()
         .await?;
 
     Ok(())
 }
 
-/// Any type that is Send + Sync implements the Message marker trait by default.
-/// We also derive(Debug) here for debugging purposes.
-#[derive(Debug)]
-struct MyMessage(i32);
-
 struct SomeNode1;
 
-/// A node that will loop {} listening for a message of a specific type.
+/// A node that ticks once a second, without drifting.
 #[node]
 impl Node for SomeNode1 {
-    async fn running(&mut self, _ctx: &Context) {
-        loop {
-            let elapsed = START_TIME.elapsed();
-            println!("{elapsed:?}");
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    async fn tick(&mut self, ctx: &Context) {
+        let elapsed = START_TIME.elapsed();
+        println!("{elapsed:?}");
+
+        if let Some(stats) = ctx.tick_stats() {
+            if stats.ticks() == 5 {
+                ctx.shutdown().await;
+            }
+        }
+    }
+
+    async fn stopping(&mut self, ctx: &Context) {
+        if let Some(stats) = ctx.tick_stats() {
+            println!(
+                "{} ticks, {} overruns, mean jitter {:?}, max jitter {:?}",
+                stats.ticks(),
+                stats.overruns(),
+                stats.mean_jitter(),
+                stats.max_jitter(),
+            );
         }
     }
 }
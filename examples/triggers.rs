@@ -0,0 +1,90 @@
+//! An example of triggers: a held button runs the intake, and an emergency
+//! stop message interrupts it.
+
+use std::time::Duration;
+
+use mekena::prelude::*;
+
+#[main(signals)]
+async fn main(system: System) -> Result<(), miette::Error> {
+    system
+        .add_node(CommandScheduler::new())
+        .add_node(Intake)
+        .add_node(Joystick)
+        .start()
+        .await?;
+
+    Ok(())
+}
+
+state_key!(BUTTON: bool = "button");
+
+/// Sent when the emergency stop is pressed.
+#[derive(Debug)]
+struct EmergencyStop;
+
+/// Owns the intake motor.
+struct Intake;
+
+#[node]
+impl Node for Intake {
+    async fn starting(&mut self, ctx: &Context) {
+        ctx.bind(
+            Trigger::new(|ctx| ctx.get(&BUTTON).unwrap().map_or(false, |pressed| *pressed))
+                .debounce(Duration::from_millis(50))
+                .while_true(|| Spin),
+        );
+
+        ctx.bind(Trigger::on_message::<EmergencyStop>(ctx).on_true(|| Stop));
+    }
+}
+
+/// Presses and releases the button, as a driver would.
+struct Joystick;
+
+#[node]
+impl Node for Joystick {
+    async fn running(&mut self, ctx: &Context) {
+        for pressed in [true, false, true] {
+            ctx.insert(&BUTTON, pressed).unwrap();
+            println!("Button {}", if pressed { "pressed" } else { "released" });
+            tokio::time::sleep(Duration::from_millis(300)).await;
+        }
+
+        println!("Emergency stop!");
+        ctx.send(EmergencyStop).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        ctx.shutdown().await;
+    }
+}
+
+struct Spin;
+
+#[command]
+impl Command for Spin {
+    fn requirements(&self) -> Vec<Resource> {
+        vec![Resource::of::<Intake>()]
+    }
+
+    async fn initialize(&mut self, _ctx: &Context) {
+        println!("Intake spinning");
+    }
+
+    async fn end(&mut self, _ctx: &Context, _interrupted: bool) {
+        println!("Intake stopped");
+    }
+}
+
+struct Stop;
+
+#[command]
+impl Command for Stop {
+    fn requirements(&self) -> Vec<Resource> {
+        vec![Resource::of::<Intake>()]
+    }
+
+    fn is_finished(&mut self, _ctx: &Context) -> bool {
+        true
+    }
+}
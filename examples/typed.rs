@@ -0,0 +1,50 @@
+//! An example of declaring a system with `system!`, which calls the nodes'
+//! hooks without dynamic dispatch, and checks that every message a node
+//! receives is sent by some node.
+//!
+//! Try emptying the sensor's `sends(..)`: the example no longer compiles, since
+//! nothing would send the logger its readings.
+
+use std::time::Duration;
+
+use mekena::prelude::*;
+
+#[main(signals)]
+async fn main(system: System) -> Result<(), miette::Error> {
+    system! {
+        in system;
+
+        Sensor,
+        Logger,
+    }
+    .start()
+    .await?;
+
+    Ok(())
+}
+
+struct Reading(f64);
+
+struct Sensor;
+
+#[node(sends(Reading))]
+impl Node for Sensor {
+    async fn running(&mut self, ctx: &Context) {
+        for i in 0..5 {
+            ctx.send(Reading(f64::from(i) * 0.5)).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(400)).await;
+        }
+    }
+}
+
+struct Logger;
+
+#[node(receives(Reading))]
+impl Node for Logger {
+    async fn running(&mut self, ctx: &Context) {
+        for _ in 0..5 {
+            let reading = ctx.recv::<Reading>().await.unwrap();
+            println!("Reading: {}", reading.0);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! An example of a watchdog catching a control loop that overruns its budget,
+//! and a sensor whose heartbeat stops. Misses are printed by a node watching
+//! the system's events.
+
+use std::time::Duration;
+
+use mekena::prelude::*;
+
+#[main(signals)]
+async fn main(system: System) -> Result<(), miette::Error> {
+    system
+        .add_node_with(
+            ControlLoop { ticks: 0 },
+            NodeConfig::new()
+                .period(Duration::from_millis(100))
+                .watchdog(
+                    Watchdog::new()
+                        .budget(Duration::from_millis(50))
+                        .action(WatchdogAction::Restart),
+                ),
+        )
+        .add_node_with(
+            Sensor,
+            NodeConfig::new().watchdog(
+                Watchdog::new()
+                    .heartbeat(Duration::from_millis(300))
+                    .action(WatchdogAction::DisableOutputs),
+            ),
+        )
+        .add_node(Monitor)
+        .start()
+        .await?;
+
+    Ok(())
+}
+
+struct ControlLoop {
+    ticks: u32,
+}
+
+#[node]
+impl Node for ControlLoop {
+    async fn starting(&mut self, _ctx: &Context) {
+        println!("Control loop starting...");
+    }
+
+    async fn tick(&mut self, _ctx: &Context) {
+        self.ticks += 1;
+
+        // Every tenth tick stalls, e.g. waiting on a slow bus.
+        if self.ticks % 10 == 0 {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn stopping(&mut self, _ctx: &Context) {
+        println!("Control loop stopping after {} ticks...", self.ticks);
+    }
+}
+
+struct Sensor;
+
+#[node]
+impl Node for Sensor {
+    async fn running(&mut self, ctx: &Context) {
+        for _ in 0..5 {
+            ctx.heartbeat();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        // The sensor hangs, and stops sending heartbeats.
+        std::future::pending::<()>().await;
+    }
+
+    async fn pause(&mut self, _ctx: &Context) {
+        println!("Sensor outputs disabled.");
+    }
+}
+
+/// Prints every missed deadline, then shuts the system down after a while.
+struct Monitor;
+
+#[node]
+impl Node for Monitor {
+    async fn running(&mut self, ctx: &Context) {
+        let mut events = ctx.events();
+        let watch = async {
+            while let Some(event) = events.next().await {
+                if let LifecycleEvent::Node {
+                    name,
+                    event: NodeEvent::DeadlineMissed(miss),
+                    ..
+                } = event
+                {
+                    println!("{name} missed a deadline: {miss}.");
+                }
+            }
+        };
+
+        tokio::select! {
+            _ = watch => {},
+            _ = tokio::time::sleep(Duration::from_secs(3)) => {},
+        }
+        ctx.shutdown().await;
+    }
+}
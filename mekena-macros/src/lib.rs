@@ -1,4 +1,4 @@
-use darling::FromMeta;
+use darling::{util::PathList, FromMeta};
 use proc_macro2::TokenStream;
 use quote::quote;
 use syn::{
@@ -6,11 +6,22 @@ use syn::{
     Pat, ReturnType, Type, TypeTuple,
 };
 
+mod system;
+
 #[derive(Debug, FromMeta)]
 struct MainMacroArgs {
     /// The path to Tokio.
     #[darling(default)]
     tokio: Option<String>,
+
+    /// Whether to handle OS signals. See `System::handle_signals`.
+    #[darling(default)]
+    signals: bool,
+
+    /// Whether SIGHUP should broadcast a `LifecycleEvent::Reload` rather than
+    /// shut down. Implies `signals`.
+    #[darling(default)]
+    reload_on_hangup: bool,
 }
 
 #[derive(Debug, FromMeta)]
@@ -18,9 +29,27 @@ struct NodeMacroArgs {
     /// The path to `async_trait`.
     #[darling(default)]
     async_trait: Option<String>,
+
+    /// The message types the node sends.
+    #[darling(default)]
+    sends: Option<PathList>,
+
+    /// The message types the node receives.
+    #[darling(default)]
+    receives: Option<PathList>,
+}
+
+#[derive(Debug, FromMeta)]
+struct CommandMacroArgs {
+    /// The path to `async_trait`.
+    #[darling(default)]
+    async_trait: Option<String>,
 }
 
 /// The `mekena::main` macro, meant to be called on the main function of a program.
+///
+/// The process exit code is taken from the system's `ShutdownReason` if it is
+/// not a clean exit, and from the function's return value otherwise.
 #[proc_macro_attribute]
 pub fn main(
     args: proc_macro::TokenStream,
@@ -76,15 +105,28 @@ pub fn main(
 
     let function_contents = function.block;
 
+    let reload_on_hangup = args.reload_on_hangup;
+    let signals = if args.signals || reload_on_hangup {
+        quote! {
+            .handle_signals(
+                mekena::signal::SignalConfig::new().reload_on_hangup(#reload_on_hangup)
+            )
+        }
+    } else {
+        quote! {}
+    };
+
     quote! {
         // An unfortunate workaround for Tokio's macro, since it can't parse
         // crate = "mekena::re::tokio".
         use mekena::re::tokio as mekena_tokio;
 
         #[#tokio::main(crate = #tokio_stringified)]
-        async fn main() -> #function_output {
-            let mut #system_name = mekena::system::System::new();
-            #function_contents
+        async fn main() -> ::std::process::ExitCode {
+            let mut #system_name = mekena::system::System::new()#signals;
+            let exit_status = #system_name.exit_status();
+            let output: #function_output = async move #function_contents.await;
+            exit_status.report(output)
         }
     }
     .into()
@@ -92,6 +134,11 @@ pub fn main(
 
 /// The `mekena::node` macro, meant to be called on nodes and basically expands
 /// to `mekena::re::async_trait::async_trait`.
+///
+/// On an `impl Node for ..` given `sends(..)` or `receives(..)`, it also
+/// implements `mekena::wiring::Messages` from the message types in them, so
+/// that the node can be put in a `system!`. Leave both out to implement it by
+/// hand.
 #[proc_macro_attribute]
 pub fn node(
     args: proc_macro::TokenStream,
@@ -107,12 +154,55 @@ pub fn node(
         }
     };
 
-    let async_trait: TokenStream = args
-        .async_trait
-        .as_deref()
-        .unwrap_or("mekena::re::async_trait")
-        .parse()
-        .unwrap();
+    let messages = match &item {
+        Item::Impl(implementation)
+            if implementation.trait_.is_some()
+                && (args.sends.is_some() || args.receives.is_some()) =>
+        {
+            let (impl_generics, _, where_clause) = implementation.generics.split_for_impl();
+            let self_ty = &implementation.self_ty;
+            let sends = args.sends.iter().flat_map(|sends| sends.iter());
+            let receives = args.receives.iter().flat_map(|receives| receives.iter());
+
+            quote! {
+                impl #impl_generics mekena::wiring::Messages for #self_ty #where_clause {
+                    type Sends = (#(#sends,)*);
+                    type Receives = (#(#receives,)*);
+                }
+            }
+        }
+        _ => quote! {},
+    };
+
+    let async_trait = async_trait_path(args.async_trait);
+
+    quote! {
+        #[#async_trait::async_trait(?Send)]
+        #item
+
+        #messages
+    }
+    .into()
+}
+
+/// The `mekena::command` macro, meant to be called on commands. Like
+/// `mekena::node`, it basically expands to `mekena::re::async_trait::async_trait`.
+#[proc_macro_attribute]
+pub fn command(
+    args: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let attr_args = parse_macro_input!(args as AttributeArgs);
+    let item = parse_macro_input!(input as Item);
+
+    let args = match CommandMacroArgs::from_list(&attr_args) {
+        Ok(v) => v,
+        Err(e) => {
+            return proc_macro::TokenStream::from(e.write_errors());
+        }
+    };
+
+    let async_trait = async_trait_path(args.async_trait);
 
     quote! {
         #[#async_trait::async_trait(?Send)]
@@ -120,3 +210,30 @@ pub fn node(
     }
     .into()
 }
+
+fn async_trait_path(path: Option<String>) -> TokenStream {
+    path.as_deref()
+        .unwrap_or("mekena::re::async_trait")
+        .parse()
+        .unwrap()
+}
+
+/// The `mekena::system!` macro, which adds nodes to a system as one statically
+/// typed `mekena::wiring::Group`, whose hooks are called without dynamic
+/// dispatch.
+///
+/// Every node must implement `mekena::wiring::Messages`, usually through
+/// `#[node(sends(..), receives(..))]`, and the macro checks at compile time
+/// that every message type a node receives is sent by a node of the group. A
+/// message that isn't fails the build with an error about a missing `is_sent`
+/// method on `Received<Message, ..>`. Messages sent from outside the group's
+/// nodes, e.g. through a `SystemHandle`, are declared up front with
+/// `external(..);`. Nodes are added to a new system, or to an existing one
+/// given with `in system;`.
+///
+/// The result is a plain `System`, so nodes that can't be declared this way
+/// can still be added with `add_node` afterwards.
+#[proc_macro]
+pub fn system(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    system::expand(parse_macro_input!(input as system::SystemInput)).into()
+}
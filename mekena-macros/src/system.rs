@@ -0,0 +1,131 @@
+//! The `system!` macro, which adds nodes to a system as one statically typed
+//! group. The messages each node sends and receives come from its type, see
+//! `mekena::wiring`.
+
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    spanned::Spanned,
+    Expr, Token, Type,
+};
+
+/// The most nodes a group can hold, as `mekena::wiring` implements groups for
+/// tuples of up to this many nodes.
+const MAX_NODES: usize = 12;
+
+/// The most messages a node can receive, as `mekena::wiring` implements
+/// message sets for tuples of up to this many messages.
+const MAX_MESSAGES: usize = 12;
+
+mod kw {
+    syn::custom_keyword!(external);
+}
+
+pub struct SystemInput {
+    /// The system to add nodes to, if not a new one.
+    base: Option<Expr>,
+    /// Message types sent from outside the group, e.g. through a handle.
+    external: Vec<Type>,
+    nodes: Punctuated<Expr, Token![,]>,
+}
+
+impl Parse for SystemInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let base = if input.peek(Token![in]) {
+            input.parse::<Token![in]>()?;
+            let base = input.parse()?;
+            input.parse::<Token![;]>()?;
+            Some(base)
+        } else {
+            None
+        };
+
+        let external = if input.peek(kw::external) {
+            input.parse::<kw::external>()?;
+            let types = types(input)?;
+            input.parse::<Token![;]>()?;
+            types
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            base,
+            external,
+            nodes: Punctuated::parse_terminated(input)?,
+        })
+    }
+}
+
+/// Parse a parenthesized, comma-separated list of types.
+fn types(input: ParseStream) -> syn::Result<Vec<Type>> {
+    let content;
+    parenthesized!(content in input);
+
+    Ok(Punctuated::<Type, Token![,]>::parse_terminated(&content)?
+        .into_iter()
+        .collect())
+}
+
+pub fn expand(input: SystemInput) -> TokenStream {
+    let base = match &input.base {
+        Some(base) => quote! { #base },
+        None => quote! { mekena::system::System::new() },
+    };
+
+    if input.nodes.is_empty() {
+        return base;
+    }
+
+    if let Some(node) = input.nodes.iter().nth(MAX_NODES) {
+        return syn::Error::new_spanned(
+            node,
+            format!(
+                "a `system!` can hold at most {MAX_NODES} nodes; add the rest with another \
+                 `system!` on the same system"
+            ),
+        )
+        .to_compile_error();
+    }
+
+    let nodes = input.nodes.iter();
+    let external = input.external.iter();
+
+    // Check every message each node could receive, as the nodes' types aren't
+    // known here. Those past the end of a node's messages are `Unused`, which
+    // every group sends.
+    let checks = input.nodes.iter().enumerate().map(|(n, node)| {
+        let node_position = position(n);
+        let checks = (0..MAX_MESSAGES).map(|k| {
+            let message_position = position(k);
+            quote_spanned! {node.span()=>
+                (&&&&&&&&&&&&&&received::<_, _, #node_position, #message_position, _>(&group))
+                    .is_sent();
+            }
+        });
+
+        quote! { #(#checks)* }
+    });
+
+    quote! {
+        {
+            let group = mekena::wiring::Group::new((#(#nodes,)*))
+                .external::<(#(#external,)*)>();
+            {
+                #[allow(unused_imports)]
+                use mekena::wiring::check::*;
+                #(#checks)*
+            }
+            #base.add_group(group)
+        }
+    }
+}
+
+/// The type-level position `index`, from `mekena::wiring::check`.
+fn position(index: usize) -> TokenStream {
+    let position = quote::format_ident!("P{index}");
+    quote! { mekena::wiring::check::#position }
+}
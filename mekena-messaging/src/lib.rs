@@ -2,6 +2,6 @@ pub mod mailbox;
 pub mod message;
 
 pub mod prelude {
-    pub use crate::mailbox::{Mailbox, MailboxError};
+    pub use crate::mailbox::{Copies, Mailbox, MailboxError, Tap};
     pub use crate::message::Message;
 }
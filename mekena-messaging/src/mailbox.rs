@@ -7,7 +7,13 @@
 //!
 //! [`Mailbox`] implements Send/Sync, so it can safely be sent across threads.
 
-use std::any::Any;
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
 
 use flume::{Receiver, Sender};
 
@@ -20,12 +26,21 @@ use crate::message::Message;
 /// collection of [`flume`] channels, it stores any type T as
 /// [`std::any::Any`], but checks to make sure your type is right with
 /// `downcast`.
-#[derive(Debug)]
+///
+/// Clones share the same underlying channels.
+#[derive(Clone)]
 pub struct Mailbox {
     sender: Sender<Box<dyn Any + Send + Sync>>,
     receiver: Receiver<Box<dyn Any + Send + Sync>>,
+    /// Shown every message of their type. See [`Mailbox::tap`] and
+    /// [`Mailbox::copies`].
+    taps: Arc<Mutex<HashMap<TypeId, Vec<TapFn>>>>,
 }
 
+/// Shown a message sent to a [`Mailbox`]. Returns `false` once nobody is
+/// listening anymore.
+type TapFn = Box<dyn Fn(&(dyn Any + Send + Sync)) -> bool + Send + Sync>;
+
 unsafe impl Send for Mailbox {}
 unsafe impl Sync for Mailbox {}
 
@@ -33,28 +48,132 @@ impl Mailbox {
     /// Construct a new, blank [`Mailbox`].
     pub fn new() -> Self {
         let (sender, receiver) = flume::unbounded();
-        Self { sender, receiver }
+        Self {
+            sender,
+            receiver,
+            taps: Arc::default(),
+        }
     }
 
     /// Send any message: [`Message`] to the mailbox.
     pub async fn send<M: Message + 'static>(&self, message: M) -> Result<(), MailboxError> {
-        self.sender.send_async(Box::new(message)).await?;
+        if let Some(taps) = self
+            .taps
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_mut(&TypeId::of::<M>())
+        {
+            // Forget the taps that were dropped.
+            taps.retain(|tap| tap(&message));
+        }
 
+        self.sender.send_async(Box::new(message)).await?;
         Ok(())
     }
 
+    /// Count the messages of type M sent from now on, without receiving them,
+    /// so that they are still delivered to whoever [`recv`](Mailbox::recv)s
+    /// them.
+    pub fn tap<M: Message + 'static>(&self) -> Tap<M> {
+        let (sender, receiver) = flume::unbounded();
+        self.add_tap::<M>(Box::new(move |_| sender.send(()).is_ok()));
+
+        Tap {
+            receiver,
+            message: PhantomData,
+        }
+    }
+
+    /// Receive copies of messages sent from now on, without receiving them,
+    /// so that they are still delivered to whoever [`recv`](Mailbox::recv)s
+    /// them. No types are copied until they are added with [`Copies::add`].
+    pub fn copies(&self) -> Copies {
+        let (sender, receiver) = flume::unbounded();
+
+        Copies {
+            mailbox: self.clone(),
+            sender,
+            receiver,
+        }
+    }
+
+    fn add_tap<M: Message + 'static>(&self, tap: TapFn) {
+        self.taps
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(TypeId::of::<M>())
+            .or_default()
+            .push(tap);
+    }
+
     /// Asynchronously wait for a new message with type M: [`Message`].
     pub async fn recv<M: Message + 'static>(&self) -> Result<Box<M>, MailboxError> {
         loop {
             let received = self.receiver.recv_async().await?;
-            match received.downcast::<M>() {
-                Ok(x) => return Ok(x),
-                _ => (),
-            };
+            if let Ok(x) = received.downcast::<M>() {
+                return Ok(x);
+            }
         }
     }
 }
 
+/// Counts the messages of type M sent to a [`Mailbox`], from
+/// [`Mailbox::tap`].
+#[derive(Debug)]
+pub struct Tap<M> {
+    receiver: Receiver<()>,
+    message: PhantomData<fn() -> M>,
+}
+
+impl<M> Tap<M> {
+    /// How many messages were sent since the tap was made, or since the last
+    /// call.
+    pub fn take(&self) -> usize {
+        self.receiver.drain().count()
+    }
+}
+
+/// Copies of the messages of some types sent to a [`Mailbox`], from
+/// [`Mailbox::copies`].
+#[derive(Debug)]
+pub struct Copies {
+    mailbox: Mailbox,
+    sender: Sender<Box<dyn Any + Send + Sync>>,
+    receiver: Receiver<Box<dyn Any + Send + Sync>>,
+}
+
+impl Copies {
+    /// Copy messages of type M too, from now on.
+    pub fn add<M: Message + Clone + 'static>(&self) {
+        let sender = self.sender.clone();
+        self.mailbox
+            .add_tap::<M>(Box::new(move |message| match message.downcast_ref::<M>() {
+                Some(message) => sender.send(Box::new(message.clone())).is_ok(),
+                None => true,
+            }));
+    }
+
+    /// Asynchronously wait for a copy of the next message of any added type.
+    pub async fn recv(&self) -> Result<Box<dyn Any + Send + Sync>, MailboxError> {
+        Ok(self.receiver.recv_async().await?)
+    }
+}
+
+impl fmt::Debug for Mailbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mailbox")
+            .field("sender", &self.sender)
+            .field("receiver", &self.receiver)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Mailbox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(thiserror::Error, miette::Diagnostic, Debug)]
 pub enum MailboxError {
     #[error(transparent)]
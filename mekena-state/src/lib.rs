@@ -3,16 +3,134 @@
 //! Stores state as a DashMap<dyn Any + Send + Sync>, but uses `downcast` to
 //! ensure safe typing.
 
-use std::any::Any;
+use std::{
+    any::{type_name, Any, TypeId},
+    fmt,
+    marker::PhantomData,
+};
 
 use dashmap::{
-    mapref::one::{Ref, RefMut},
+    mapref::{
+        entry::Entry,
+        one::{MappedRef, MappedRefMut},
+    },
     DashMap,
 };
 
+/// A reference to a value of type `V` in a [`StateManager`].
+pub type StateRef<'a, V> = MappedRef<'a, String, Value, V>;
+
+/// A mutable reference to a value of type `V` in a [`StateManager`].
+pub type StateRefMut<'a, V> = MappedRefMut<'a, String, Value, V>;
+
+/// A key to values of type `V`, so that they can be looked up without naming
+/// their type, or mistyping their key. Usually declared with [`state_key!`].
+pub struct StateKey<V> {
+    name: &'static str,
+    value: PhantomData<fn() -> V>,
+}
+
+impl<V> StateKey<V> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            value: PhantomData,
+        }
+    }
+
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<V> Clone for StateKey<V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<V> Copy for StateKey<V> {}
+
+impl<V> fmt::Debug for StateKey<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StateKey")
+            .field(&self.name)
+            .field(&type_name::<V>())
+            .finish()
+    }
+}
+
+/// Declare a [`StateKey`] constant. The key's name is the constant's, unless
+/// one is given.
+///
+/// `state_key!(pub ODOMETRY: Odometry);` declares
+/// `pub const ODOMETRY: StateKey<Odometry>` named `"ODOMETRY"`, and
+/// `state_key!(DISTANCE: f64 = "distance");` one named `"distance"`.
+#[macro_export]
+macro_rules! state_key {
+    ($vis:vis $name:ident: $value:ty) => {
+        $crate::state_key!($vis $name: $value = stringify!($name));
+    };
+    ($vis:vis $name:ident: $value:ty = $key:expr) => {
+        $vis const $name: $crate::StateKey<$value> = $crate::StateKey::new($key);
+    };
+}
+
+/// Something values of type `V` can be looked up by: a [`StateKey<V>`], or a
+/// string, for keys only known at runtime.
+pub trait Key<V> {
+    fn name(&self) -> &str;
+}
+
+impl<V> Key<V> for StateKey<V> {
+    fn name(&self) -> &str {
+        self.name
+    }
+}
+
+impl<V> Key<V> for str {
+    fn name(&self) -> &str {
+        self
+    }
+}
+
+impl<V> Key<V> for String {
+    fn name(&self) -> &str {
+        self
+    }
+}
+
 #[derive(Debug)]
 pub struct StateManager {
-    states: DashMap<String, Box<dyn Any + Send + Sync>>,
+    states: DashMap<String, Value>,
+}
+
+/// A value in a [`StateManager`], and the name of its type.
+#[derive(Debug)]
+pub struct Value {
+    value: Box<dyn Any + Send + Sync>,
+    type_name: &'static str,
+}
+
+impl Value {
+    fn new<V: 'static + Send + Sync>(value: V) -> Self {
+        Self {
+            value: Box::new(value),
+            type_name: type_name::<V>(),
+        }
+    }
+
+    fn check<V: 'static>(&self, key: &str) -> Result<(), StateError> {
+        if self.value.is::<V>() {
+            Ok(())
+        } else {
+            Err(StateError::TypeMismatch {
+                key: key.to_string(),
+                expected: type_name::<V>(),
+                found: self.type_name,
+            })
+        }
+    }
 }
 
 impl StateManager {
@@ -23,32 +141,137 @@ impl StateManager {
     }
 
     /// Inserts a key and a value into the map. Returns the old value associated
-    /// with the key if there was one.
+    /// with the key if there was one. If the old value is of another type, it
+    /// is kept, and an error is returned instead.
+    ///
+    /// **Locking behaviour: May deadlock if called when holding any sort of
+    /// reference into the map.**. Unfortunately, this is inherited from
+    /// [`dashmap`].
+    pub fn insert<V: 'static + Send + Sync, K: Key<V> + ?Sized>(
+        &self,
+        key: &K,
+        value: V,
+    ) -> Result<Option<Box<V>>, StateError> {
+        match self.states.entry(key.name().to_string()) {
+            Entry::Occupied(mut entry) => {
+                entry.get().check::<V>(entry.key())?;
+                let old = entry.insert(Value::new(value));
+                Ok(old.value.downcast::<V>().ok())
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(Value::new(value));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Get an immutable reference to a value of type `V` in the map.
+    ///
+    /// **Locking behaviour: May deadlock if called when holding a mutable
+    /// reference into the map.** Unfortunately, this is inherited from
+    /// [`dashmap`].
+    pub fn get<V: 'static + Send + Sync, K: Key<V> + ?Sized>(
+        &self,
+        key: &K,
+    ) -> Result<Option<StateRef<'_, V>>, StateError> {
+        let entry = match self.states.get(key.name()) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        entry.check::<V>(key.name())?;
+
+        Ok(entry.try_map(|entry| entry.value.downcast_ref::<V>()).ok())
+    }
+
+    /// Get a mutable reference to a value of type `V` in the map.
+    ///
+    /// **Locking behaviour: May deadlock if called when holding any sort of
+    /// reference into the map.** Unfortunately, this is inherited from
+    /// [`dashmap`].
+    pub fn get_mut<V: 'static + Send + Sync, K: Key<V> + ?Sized>(
+        &self,
+        key: &K,
+    ) -> Result<Option<StateRefMut<'_, V>>, StateError> {
+        let entry = match self.states.get_mut(key.name()) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        entry.check::<V>(key.name())?;
+
+        Ok(entry.try_map(|entry| entry.value.downcast_mut::<V>()).ok())
+    }
+}
+
+impl Default for StateManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A reference to the resource of type `T` in [`Resources`].
+pub type ResourceRef<'a, T> = MappedRef<'a, TypeId, Box<dyn Any + Send + Sync>, T>;
+
+/// A mutable reference to the resource of type `T` in [`Resources`].
+pub type ResourceRefMut<'a, T> = MappedRefMut<'a, TypeId, Box<dyn Any + Send + Sync>, T>;
+
+/// Singleton values, at most one of each type, e.g. configuration or hardware
+/// handles.
+#[derive(Debug, Default)]
+pub struct Resources {
+    values: DashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Resources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a resource, returning the one of the same type it replaced.
     ///
     /// **Locking behaviour: May deadlock if called when holding any sort of
     /// reference into the map.**. Unfortunately, this is inherited from
     /// [`dashmap`].
-    pub fn insert<V: 'static + Send + Sync>(&self, key: String, value: V) -> Option<Box<V>> {
-        self.states
-            .insert(key, Box::new(value))
-            .map(|x| x.downcast::<V>().unwrap())
+    pub fn insert<T: 'static + Send + Sync>(&self, value: T) -> Option<Box<T>> {
+        self.values
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|old| old.downcast::<T>().ok())
     }
 
-    /// Get a immutable reference to an entry in the map
+    /// Get an immutable reference to the resource of type `T`.
     ///
     /// **Locking behaviour: May deadlock if called when holding a mutable
     /// reference into the map.** Unfortunately, this is inherited from
     /// [`dashmap`].
-    pub fn get(&self, key: String) -> Option<Ref<String, Box<dyn Any + Send + Sync>>> {
-        self.states.get(&key)
+    pub fn get<T: 'static + Send + Sync>(&self) -> Option<ResourceRef<'_, T>> {
+        self.values
+            .get(&TypeId::of::<T>())?
+            .try_map(|value| value.downcast_ref::<T>())
+            .ok()
     }
 
-    /// Get a mutable reference to an entry in the map
+    /// Get a mutable reference to the resource of type `T`.
     ///
     /// **Locking behaviour: May deadlock if called when holding any sort of
     /// reference into the map.** Unfortunately, this is inherited from
     /// [`dashmap`].
-    pub fn get_mut(&self, key: String) -> Option<RefMut<String, Box<dyn Any + Send + Sync>>> {
-        self.states.get_mut(&key)
+    pub fn get_mut<T: 'static + Send + Sync>(&self) -> Option<ResourceRefMut<'_, T>> {
+        self.values
+            .get_mut(&TypeId::of::<T>())?
+            .try_map(|value| value.downcast_mut::<T>())
+            .ok()
     }
 }
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    #[error("The state at {key:?} is a {found}, not a {expected}.")]
+    #[diagnostic(
+        code(mekena_state::type_mismatch),
+        help("Every value stored under a key must be of the same type.")
+    )]
+    TypeMismatch {
+        key: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+}
@@ -1,23 +1,143 @@
-use flume::{Receiver, Sender};
+//! Broadcast, hierarchical shutdown signalling.
+//!
+//! A [`ShutdownManager`] wraps a [`CancellationToken`]. Any number of tasks
+//! may wait on the same manager, and all of them are woken once it is shut
+//! down. Shutting down is idempotent.
+//!
+//! Managers form a tree: [`ShutdownManager::child`] derives a new manager that
+//! is shut down whenever its parent is, but that can also be shut down on its
+//! own without affecting the parent. This is useful for scoping the lifetime
+//! of a single node or subsystem to that of the whole system.
+//!
+//! Every shutdown carries a [`ShutdownReason`], which can later be mapped to a
+//! process exit code.
 
-#[derive(Debug)]
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use tokio_util::sync::CancellationToken;
+
+/// A cloneable handle to a shutdown signal. Clones share the same signal.
+#[derive(Clone, Debug, Default)]
 pub struct ShutdownManager {
-    sender: Sender<()>,
-    receiver: Receiver<()>,
+    token: CancellationToken,
+    reason: Arc<Mutex<Option<ShutdownReason>>>,
+    parent: Option<Box<ShutdownManager>>,
 }
 
 impl ShutdownManager {
+    /// Construct a new, root [`ShutdownManager`].
     pub fn new() -> Self {
-        let (sender, receiver) = flume::bounded(1);
+        Self::default()
+    }
+
+    /// Derive a child manager. The child is shut down when `self` is, but
+    /// shutting down the child does not shut down `self`.
+    pub fn child(&self) -> Self {
+        Self {
+            token: self.token.child_token(),
+            reason: Arc::default(),
+            parent: Some(Box::new(self.clone())),
+        }
+    }
+
+    /// Signal a shutdown with [`ShutdownReason::Requested`]. See
+    /// [`ShutdownManager::shutdown_with`].
+    pub fn shutdown(&self) {
+        self.shutdown_with(ShutdownReason::Requested)
+    }
+
+    /// Signal a shutdown, waking every waiter. Only the first reason is kept;
+    /// calling this more than once has no further effect.
+    pub fn shutdown_with(&self, reason: ShutdownReason) {
+        {
+            let mut slot = self.reason.lock().unwrap_or_else(|e| e.into_inner());
+            if slot.is_none() && !self.token.is_cancelled() {
+                *slot = Some(reason);
+            }
+        }
+
+        self.token.cancel()
+    }
+
+    /// The reason this manager was shut down, if it has been. A child that was
+    /// shut down by its parent reports the parent's reason.
+    pub fn reason(&self) -> Option<ShutdownReason> {
+        let own = self
+            .reason
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
 
-        Self { sender, receiver }
+        own.or_else(|| self.parent.as_ref().and_then(|p| p.reason()))
     }
 
-    pub async fn shutdown(&self) {
-        self.sender.send_async(()).await.unwrap()
+    /// Returns `true` if this manager, or any of its ancestors, has been shut
+    /// down.
+    pub fn is_shutdown(&self) -> bool {
+        self.token.is_cancelled()
     }
 
+    /// Wait until this manager, or any of its ancestors, is shut down. Returns
+    /// immediately if that has already happened.
     pub async fn await_shutdown(&self) {
-        self.receiver.recv_async().await.unwrap()
+        self.token.cancelled().await
+    }
+}
+
+/// Why a shutdown happened.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShutdownReason {
+    /// Every node finished on its own, without anyone asking for a shutdown.
+    Completed,
+    /// A shutdown was requested as part of normal operation.
+    Requested,
+    /// A fault caused the shutdown.
+    Error(String),
+    /// The process received the given OS signal.
+    Signal(i32),
+    /// Something took too long.
+    Timeout,
+    /// A user-defined reason, with the exit code it should map to.
+    Custom { code: u8, message: String },
+}
+
+impl ShutdownReason {
+    /// Shorthand for [`ShutdownReason::Error`] from anything printable.
+    pub fn error(error: impl fmt::Display) -> Self {
+        Self::Error(error.to_string())
+    }
+
+    /// The process exit code this reason maps to. Clean exits map to `0`,
+    /// errors to `1`, signals to `128 + signal` and timeouts to `124`,
+    /// following the usual shell conventions.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Completed | Self::Requested => 0,
+            Self::Error(_) => 1,
+            Self::Signal(signal) => 128u8.saturating_add(*signal as u8),
+            Self::Timeout => 124,
+            Self::Custom { code, .. } => *code,
+        }
+    }
+
+    /// Returns `true` if this reason represents a clean exit.
+    pub fn is_success(&self) -> bool {
+        self.exit_code() == 0
+    }
+}
+
+impl fmt::Display for ShutdownReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Completed => write!(f, "all nodes completed"),
+            Self::Requested => write!(f, "shutdown requested"),
+            Self::Error(e) => write!(f, "error: {e}"),
+            Self::Signal(signal) => write!(f, "received signal {signal}"),
+            Self::Timeout => write!(f, "timed out"),
+            Self::Custom { code, message } => write!(f, "{message} (exit code {code})"),
+        }
     }
 }
@@ -0,0 +1,557 @@
+//! Behavior trees, for autonomous routines made of smaller steps.
+//!
+//! A [`Behavior`] is a tree of composites ([`sequence`], [`selector`],
+//! [`parallel`]), decorators ([`invert`], [`retry`], [`timeout`]), and leaves:
+//! [`action`]s, which are async functions of a [`Context`] that succeed or
+//! fail, and [`condition`]s, which are checked without waiting. A
+//! [`BehaviorTree`] node ticks the tree at a fixed rate until it succeeds or
+//! fails, and keeps a [`Trace`] of the last tick.
+//!
+//! An action spans as many ticks as it takes: each tick polls it once, and it
+//! is reported as running until it finishes. Actions a tick no longer reaches,
+//! e.g. because a timeout expired, are dropped.
+//!
+//! Trees can also be loaded from a serialized [`Description`], with their
+//! leaves looked up by name in [`Leaves`].
+//!
+//! [`sequence`]: Behavior::sequence
+//! [`selector`]: Behavior::selector
+//! [`parallel`]: Behavior::parallel
+//! [`invert`]: Behavior::invert
+//! [`retry`]: Behavior::retry
+//! [`timeout`]: Behavior::timeout
+//! [`action`]: Behavior::action
+//! [`condition`]: Behavior::condition
+
+use std::{collections::HashMap, fmt, future::Future, rc::Rc, time::Duration};
+
+use futures::{future::LocalBoxFuture, FutureExt};
+use tokio::time::{Instant, MissedTickBehavior};
+
+use crate::{context::Context, node::Node};
+
+type ActionFn = Rc<dyn Fn(Context) -> LocalBoxFuture<'static, bool>>;
+type ConditionFn = Rc<dyn Fn(&Context) -> bool>;
+
+/// The result of ticking a [`Behavior`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Status {
+    Success,
+    Failure,
+    /// Not finished yet: tick it again.
+    Running,
+}
+
+/// A behavior tree, or a part of one. See the [module docs](self).
+pub struct Behavior {
+    name: String,
+    kind: Kind,
+}
+
+enum Kind {
+    Sequence {
+        children: Vec<Behavior>,
+        current: usize,
+    },
+    Selector {
+        children: Vec<Behavior>,
+        current: usize,
+    },
+    Parallel {
+        children: Vec<Behavior>,
+        succeeded: Vec<bool>,
+    },
+    Invert(Box<Behavior>),
+    Retry {
+        child: Box<Behavior>,
+        attempts: u32,
+        failures: u32,
+    },
+    Timeout {
+        child: Box<Behavior>,
+        duration: Duration,
+        started: Option<Instant>,
+    },
+    Action {
+        make: ActionFn,
+        running: Option<LocalBoxFuture<'static, bool>>,
+    },
+    Condition(ConditionFn),
+}
+
+impl Behavior {
+    /// Tick each child in turn, failing as soon as one fails, and succeeding
+    /// once all have.
+    pub fn sequence(children: Vec<Behavior>) -> Self {
+        Self::new(
+            "sequence",
+            Kind::Sequence {
+                children,
+                current: 0,
+            },
+        )
+    }
+
+    /// Tick each child in turn, succeeding as soon as one succeeds, and
+    /// failing once all have.
+    pub fn selector(children: Vec<Behavior>) -> Self {
+        Self::new(
+            "selector",
+            Kind::Selector {
+                children,
+                current: 0,
+            },
+        )
+    }
+
+    /// Tick every child at once, failing as soon as one fails, and succeeding
+    /// once all have.
+    pub fn parallel(children: Vec<Behavior>) -> Self {
+        let succeeded = vec![false; children.len()];
+        Self::new(
+            "parallel",
+            Kind::Parallel {
+                children,
+                succeeded,
+            },
+        )
+    }
+
+    /// A leaf that runs an async function until it returns whether it
+    /// succeeded.
+    pub fn action<F>(name: impl Into<String>, action: impl Fn(Context) -> F + 'static) -> Self
+    where
+        F: Future<Output = bool> + 'static,
+    {
+        Self::new(
+            name,
+            Kind::Action {
+                make: Rc::new(move |ctx| action(ctx).boxed_local()),
+                running: None,
+            },
+        )
+    }
+
+    /// A leaf that succeeds if `condition` holds when ticked, and fails
+    /// otherwise.
+    pub fn condition(
+        name: impl Into<String>,
+        condition: impl Fn(&Context) -> bool + 'static,
+    ) -> Self {
+        Self::new(name, Kind::Condition(Rc::new(condition)))
+    }
+
+    /// Succeed when this fails, and fail when this succeeds.
+    pub fn invert(self) -> Self {
+        Self::new("invert", Kind::Invert(Box::new(self)))
+    }
+
+    /// Run this again when it fails, failing once it has failed `attempts`
+    /// times.
+    pub fn retry(self, attempts: u32) -> Self {
+        Self::new(
+            "retry",
+            Kind::Retry {
+                child: Box::new(self),
+                attempts,
+                failures: 0,
+            },
+        )
+    }
+
+    /// Fail if this hasn't finished `duration` after it was first ticked.
+    pub fn timeout(self, duration: Duration) -> Self {
+        Self::new(
+            "timeout",
+            Kind::Timeout {
+                child: Box::new(self),
+                duration,
+                started: None,
+            },
+        )
+    }
+
+    /// Name this in traces, instead of by its kind.
+    pub fn named(self, name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..self
+        }
+    }
+
+    /// Build a tree from its description, looking its leaves up in `leaves`.
+    pub fn load(description: &Description, leaves: &Leaves) -> Result<Self, BehaviorError> {
+        let all = |children: &[Description]| {
+            children
+                .iter()
+                .map(|child| Self::load(child, leaves))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok(match description {
+            Description::Sequence(children) => Self::sequence(all(children)?),
+            Description::Selector(children) => Self::selector(all(children)?),
+            Description::Parallel(children) => Self::parallel(all(children)?),
+            Description::Invert(child) => Self::load(child, leaves)?.invert(),
+            Description::Retry { attempts, child } => Self::load(child, leaves)?.retry(*attempts),
+            Description::Timeout { millis, child } => {
+                Self::load(child, leaves)?.timeout(Duration::from_millis(*millis))
+            }
+            Description::Leaf(name) => {
+                let kind = if let Some(make) = leaves.actions.get(name) {
+                    Kind::Action {
+                        make: make.clone(),
+                        running: None,
+                    }
+                } else if let Some(condition) = leaves.conditions.get(name) {
+                    Kind::Condition(condition.clone())
+                } else {
+                    return Err(BehaviorError::UnknownLeaf { name: name.clone() });
+                };
+                Self::new(name.clone(), kind)
+            }
+        })
+    }
+
+    fn new(name: impl Into<String>, kind: Kind) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+        }
+    }
+
+    /// Tick this once, recording it and everything it ticks in `trace`.
+    pub fn tick(&mut self, ctx: &Context, trace: &mut Trace) -> Status {
+        let index = trace.entries.len();
+        trace.entries.push(TraceEntry {
+            depth: trace.depth,
+            name: self.name.clone(),
+            status: Status::Running,
+        });
+
+        trace.depth += 1;
+        let status = self.tick_kind(ctx, trace);
+        trace.depth -= 1;
+
+        trace.entries[index].status = status;
+        if status != Status::Running {
+            self.halt();
+        }
+        status
+    }
+
+    fn tick_kind(&mut self, ctx: &Context, trace: &mut Trace) -> Status {
+        match &mut self.kind {
+            Kind::Sequence { children, current } => {
+                while let Some(child) = children.get_mut(*current) {
+                    match child.tick(ctx, trace) {
+                        Status::Success => *current += 1,
+                        status => return status,
+                    }
+                }
+                Status::Success
+            }
+            Kind::Selector { children, current } => {
+                while let Some(child) = children.get_mut(*current) {
+                    match child.tick(ctx, trace) {
+                        Status::Failure => *current += 1,
+                        status => return status,
+                    }
+                }
+                Status::Failure
+            }
+            Kind::Parallel {
+                children,
+                succeeded,
+            } => {
+                for (child, succeeded) in children.iter_mut().zip(succeeded.iter_mut()) {
+                    if *succeeded {
+                        continue;
+                    }
+                    match child.tick(ctx, trace) {
+                        Status::Success => *succeeded = true,
+                        Status::Failure => return Status::Failure,
+                        Status::Running => {}
+                    }
+                }
+
+                if succeeded.iter().all(|&succeeded| succeeded) {
+                    Status::Success
+                } else {
+                    Status::Running
+                }
+            }
+            Kind::Invert(child) => match child.tick(ctx, trace) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                Status::Running => Status::Running,
+            },
+            Kind::Retry {
+                child,
+                attempts,
+                failures,
+            } => match child.tick(ctx, trace) {
+                Status::Failure => {
+                    *failures += 1;
+                    if *failures < *attempts {
+                        Status::Running
+                    } else {
+                        Status::Failure
+                    }
+                }
+                status => status,
+            },
+            Kind::Timeout {
+                child,
+                duration,
+                started,
+            } => {
+                if started.get_or_insert_with(Instant::now).elapsed() >= *duration {
+                    Status::Failure
+                } else {
+                    child.tick(ctx, trace)
+                }
+            }
+            Kind::Action { make, running } => {
+                let action = running.get_or_insert_with(|| make(ctx.clone()));
+                match action.now_or_never() {
+                    Some(true) => Status::Success,
+                    Some(false) => Status::Failure,
+                    None => Status::Running,
+                }
+            }
+            Kind::Condition(condition) => {
+                if condition(ctx) {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+        }
+    }
+
+    /// Reset this and everything under it, dropping running actions.
+    fn halt(&mut self) {
+        match &mut self.kind {
+            Kind::Sequence { children, current } | Kind::Selector { children, current } => {
+                children.iter_mut().for_each(Self::halt);
+                *current = 0;
+            }
+            Kind::Parallel {
+                children,
+                succeeded,
+            } => {
+                children.iter_mut().for_each(Self::halt);
+                succeeded
+                    .iter_mut()
+                    .for_each(|succeeded| *succeeded = false);
+            }
+            Kind::Invert(child) => child.halt(),
+            Kind::Retry {
+                child, failures, ..
+            } => {
+                child.halt();
+                *failures = 0;
+            }
+            Kind::Timeout { child, started, .. } => {
+                child.halt();
+                *started = None;
+            }
+            Kind::Action { running, .. } => *running = None,
+            Kind::Condition(_) => {}
+        }
+    }
+}
+
+impl fmt::Debug for Behavior {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Behavior");
+        debug.field("name", &self.name);
+        match &self.kind {
+            Kind::Sequence { children, .. }
+            | Kind::Selector { children, .. }
+            | Kind::Parallel { children, .. } => debug.field("children", children),
+            Kind::Invert(child) | Kind::Retry { child, .. } | Kind::Timeout { child, .. } => {
+                debug.field("child", child)
+            }
+            Kind::Action { .. } | Kind::Condition(_) => &mut debug,
+        };
+        debug.finish()
+    }
+}
+
+/// The behaviors a tick reached, in the order it reached them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Trace {
+    entries: Vec<TraceEntry>,
+    depth: usize,
+}
+
+/// A behavior reached by a tick.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// How far down the tree the behavior is, 0 being the root.
+    pub depth: usize,
+    pub name: String,
+    pub status: Status,
+}
+
+impl Trace {
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(
+                f,
+                "{:indent$}{}: {:?}",
+                "",
+                entry.name,
+                entry.status,
+                indent = entry.depth * 2
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A serializable description of a behavior tree, loaded with
+/// [`Behavior::load`].
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Description {
+    Sequence(Vec<Description>),
+    Selector(Vec<Description>),
+    Parallel(Vec<Description>),
+    Invert(Box<Description>),
+    Retry {
+        attempts: u32,
+        child: Box<Description>,
+    },
+    Timeout {
+        millis: u64,
+        child: Box<Description>,
+    },
+    /// An action or condition, by its name in [`Leaves`].
+    Leaf(String),
+}
+
+/// The leaves a [`Description`] can refer to, by name.
+#[derive(Clone, Default)]
+pub struct Leaves {
+    actions: HashMap<String, ActionFn>,
+    conditions: HashMap<String, ConditionFn>,
+}
+
+impl Leaves {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an action. See [`Behavior::action`].
+    pub fn action<F>(
+        mut self,
+        name: impl Into<String>,
+        action: impl Fn(Context) -> F + 'static,
+    ) -> Self
+    where
+        F: Future<Output = bool> + 'static,
+    {
+        self.actions
+            .insert(name.into(), Rc::new(move |ctx| action(ctx).boxed_local()));
+        self
+    }
+
+    /// Add a condition. See [`Behavior::condition`].
+    pub fn condition(
+        mut self,
+        name: impl Into<String>,
+        condition: impl Fn(&Context) -> bool + 'static,
+    ) -> Self {
+        self.conditions.insert(name.into(), Rc::new(condition));
+        self
+    }
+}
+
+impl fmt::Debug for Leaves {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Leaves")
+            .field("actions", &self.actions.keys())
+            .field("conditions", &self.conditions.keys())
+            .finish()
+    }
+}
+
+/// A node that ticks a behavior tree at a fixed rate, until it succeeds or
+/// fails.
+#[derive(Debug)]
+pub struct BehaviorTree {
+    root: Behavior,
+    period: Duration,
+    trace: Trace,
+}
+
+impl BehaviorTree {
+    /// Construct a tree that is ticked every 20ms.
+    pub fn new(root: Behavior) -> Self {
+        Self {
+            root,
+            period: Duration::from_millis(20),
+            trace: Trace::default(),
+        }
+    }
+
+    /// How often the tree is ticked. Fails if `period` is zero.
+    pub fn period(self, period: Duration) -> Result<Self, BehaviorError> {
+        if period.is_zero() {
+            return Err(BehaviorError::ZeroPeriod);
+        }
+        Ok(Self { period, ..self })
+    }
+
+    /// Tick the tree once.
+    pub fn tick(&mut self, ctx: &Context) -> Status {
+        self.trace = Trace::default();
+        self.root.tick(ctx, &mut self.trace)
+    }
+
+    /// What the last tick reached.
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Node for BehaviorTree {
+    async fn running(&mut self, ctx: &Context) {
+        let mut interval = tokio::time::interval(self.period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+            if self.tick(ctx) != Status::Running {
+                return;
+            }
+        }
+    }
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug, Clone, PartialEq, Eq)]
+pub enum BehaviorError {
+    #[error("The description refers to a leaf named {name:?}, which isn't known.")]
+    #[diagnostic(
+        code(mekena::behavior::unknown_leaf),
+        help("Add it to the `Leaves` the tree is loaded with.")
+    )]
+    UnknownLeaf { name: String },
+
+    #[error("The behavior tree has a period of zero.")]
+    #[diagnostic(
+        code(mekena::behavior::zero_period),
+        help("A tree can't be ticked continuously. Give it a period of at least a millisecond.")
+    )]
+    ZeroPeriod,
+}
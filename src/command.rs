@@ -0,0 +1,444 @@
+//! A command-based scheduler, in the style of WPILib.
+//!
+//! A [`Command`] is a unit of robot behaviour, like "drive forward two metres"
+//! or "spin up the shooter". It is [`initialize`]d once, [`execute`]d once per
+//! scheduler iteration until it [`is_finished`], then [`end`]ed.
+//!
+//! Commands require [`Resource`]s, usually the nodes that own a piece of
+//! hardware, and no two scheduled commands may require the same resource at
+//! once. When a new command needs a resource that is already claimed, the
+//! current holder's [`InterruptionBehavior`] decides whether it is interrupted
+//! (its `end` hook is called with `interrupted` set), or the new command is
+//! rejected. A resource can have a default command, which is scheduled
+//! whenever no other command holds it, e.g. to hold an arm in place.
+//!
+//! Commands are scheduled from any node with [`Context::schedule`], and run by
+//! a [`CommandScheduler`] node, which must be added to the system. They can be
+//! composed into sequences, parallel groups and more, see [`compose`], and
+//! bound to buttons and sensors with [`trigger`]s.
+//!
+//! [`initialize`]: Command::initialize
+//! [`execute`]: Command::execute
+//! [`is_finished`]: Command::is_finished
+//! [`end`]: Command::end
+//! [`Context::schedule`]: crate::context::Context::schedule
+
+use std::{
+    any::TypeId,
+    collections::{HashMap, HashSet},
+    fmt,
+    time::Duration,
+};
+
+use tokio::time::MissedTickBehavior;
+
+use crate::{context::Context, node::Node};
+
+pub mod compose;
+pub mod trigger;
+
+pub use compose::{wait, CommandExt};
+pub use trigger::Trigger;
+
+#[async_trait::async_trait(?Send)]
+pub trait Command {
+    /// The resources this command needs to itself while it is scheduled.
+    fn requirements(&self) -> Vec<Resource> {
+        Vec::new()
+    }
+
+    /// What happens when another command needs one of this command's
+    /// resources while it is scheduled.
+    fn interruption(&self) -> InterruptionBehavior {
+        InterruptionBehavior::default()
+    }
+
+    /// Called once, before the first `execute`.
+    async fn initialize(&mut self, _ctx: &Context) {}
+
+    /// Called once per scheduler iteration while the command is scheduled.
+    async fn execute(&mut self, _ctx: &Context) {}
+
+    /// Checked after every `execute`. Once this returns `true`, the command is
+    /// ended and unscheduled.
+    fn is_finished(&mut self, _ctx: &Context) -> bool {
+        false
+    }
+
+    /// Called once when the command finishes, or with `interrupted` set when
+    /// it is cancelled or interrupted by another command.
+    async fn end(&mut self, _ctx: &Context, _interrupted: bool) {}
+}
+
+/// Something a command can require, usually a node owning some hardware.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Resource(ResourceKind);
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum ResourceKind {
+    Type(TypeId, &'static str),
+    Name(String),
+}
+
+impl Resource {
+    /// The resource owned by the node of type `N`.
+    pub fn of<N: Node + 'static>() -> Self {
+        Self(ResourceKind::Type(
+            TypeId::of::<N>(),
+            std::any::type_name::<N>(),
+        ))
+    }
+
+    /// A resource identified by name.
+    pub fn named(name: impl Into<String>) -> Self {
+        Self(ResourceKind::Name(name.into()))
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            ResourceKind::Type(_, name) => write!(f, "{name}"),
+            ResourceKind::Name(name) => write!(f, "{name:?}"),
+        }
+    }
+}
+
+/// What happens when a command needs a resource that a scheduled command
+/// already holds. This is decided by the command holding it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum InterruptionBehavior {
+    /// The holder is interrupted, and the new command is scheduled.
+    #[default]
+    CancelSelf,
+    /// The new command is rejected, and the holder keeps running.
+    CancelIncoming,
+}
+
+/// Makes commands, for things that schedule a command more than once.
+pub(crate) type Factory = Box<dyn FnMut() -> Box<dyn Command>>;
+
+pub(crate) fn factory<C: Command + 'static>(mut command: impl FnMut() -> C + 'static) -> Factory {
+    Box::new(move || Box::new(command()))
+}
+
+/// Identifies a scheduled command.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CommandId(u64);
+
+impl fmt::Display for CommandId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "command #{}", self.0)
+    }
+}
+
+/// Runs scheduled commands. Add exactly one to a system that uses commands:
+/// without it, scheduled commands never run.
+#[derive(Debug)]
+pub struct CommandScheduler {
+    period: Duration,
+    running: Vec<(CommandId, Box<dyn Command>)>,
+}
+
+impl CommandScheduler {
+    /// Construct a scheduler that runs every 20ms, like WPILib's.
+    pub fn new() -> Self {
+        Self {
+            period: Duration::from_millis(20),
+            running: Vec::new(),
+        }
+    }
+
+    /// How often commands are executed. Fails if `period` is zero.
+    pub fn period(self, period: Duration) -> Result<Self, ScheduleError> {
+        if period.is_zero() {
+            return Err(ScheduleError::ZeroPeriod);
+        }
+        Ok(Self { period, ..self })
+    }
+
+    /// Run one iteration: poll triggers, end cancelled commands, schedule
+    /// default commands for free resources, initialize new commands, then
+    /// execute every command, ending those that finish.
+    async fn run_once(&mut self, ctx: &Context) {
+        let local = ctx.local();
+
+        // Triggers schedule through the queue, so it can't stay borrowed.
+        let mut triggers = local.commands().take_triggers();
+        for trigger in &mut triggers {
+            trigger.poll(ctx);
+        }
+        local.commands().restore_triggers(triggers);
+
+        let cancelled = local.commands().take_cancelled();
+        for (_, command) in self
+            .running
+            .iter_mut()
+            .filter(|(id, _)| cancelled.contains(id))
+        {
+            command.end(ctx, true).await;
+        }
+        self.running.retain(|(id, _)| !cancelled.contains(id));
+
+        local.commands().schedule_defaults();
+
+        let incoming = local.commands().take_incoming();
+        for (id, mut command) in incoming {
+            command.initialize(ctx).await;
+            self.running.push((id, command));
+        }
+
+        let mut finished = Vec::new();
+        for (id, command) in &mut self.running {
+            // A command executed earlier in this iteration may have
+            // interrupted this one.
+            if !local.commands().is_scheduled(*id) {
+                continue;
+            }
+
+            command.execute(ctx).await;
+
+            if command.is_finished(ctx) {
+                command.end(ctx, false).await;
+                local.commands().finish(*id);
+                finished.push(*id);
+            }
+        }
+        self.running.retain(|(id, _)| !finished.contains(id));
+    }
+}
+
+impl Default for CommandScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Node for CommandScheduler {
+    async fn running(&mut self, ctx: &Context) {
+        let mut interval = tokio::time::interval(self.period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+            self.run_once(ctx).await;
+        }
+    }
+
+    async fn stopping(&mut self, ctx: &Context) {
+        for (id, mut command) in self.running.drain(..) {
+            command.end(ctx, true).await;
+            ctx.local().commands().finish(id);
+        }
+    }
+}
+
+impl fmt::Debug for dyn Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Command")
+            .field("requirements", &self.requirements())
+            .finish()
+    }
+}
+
+/// The commands of a system, shared between the nodes scheduling them and the
+/// [`CommandScheduler`] running them.
+#[derive(Debug, Default)]
+pub(crate) struct Queue {
+    next_id: u64,
+    /// Scheduled, but not initialized yet.
+    incoming: Vec<(CommandId, Box<dyn Command>)>,
+    /// Incoming or running, and not cancelled.
+    scheduled: HashSet<CommandId>,
+    /// Cancelled or interrupted, but not ended yet.
+    cancelled: HashSet<CommandId>,
+    held: HashMap<Resource, (CommandId, InterruptionBehavior)>,
+    defaults: HashMap<Resource, DefaultCommand>,
+    triggers: Vec<Trigger>,
+}
+
+struct DefaultCommand {
+    make: Factory,
+    /// Made, but not scheduled yet.
+    next: Option<Box<dyn Command>>,
+}
+
+impl Queue {
+    pub fn schedule(&mut self, command: Box<dyn Command>) -> Result<CommandId, ScheduleError> {
+        let requirements = command.requirements();
+
+        let mut holders = Vec::new();
+        for resource in &requirements {
+            match self.held.get(resource) {
+                Some(&(holder, InterruptionBehavior::CancelIncoming)) => {
+                    return Err(ScheduleError::Conflict {
+                        resource: resource.to_string(),
+                        holder,
+                    })
+                }
+                Some(&(holder, InterruptionBehavior::CancelSelf)) => holders.push(holder),
+                None => {}
+            }
+        }
+
+        for holder in holders {
+            self.cancel(holder);
+        }
+
+        let id = CommandId(self.next_id);
+        self.next_id += 1;
+
+        let behavior = command.interruption();
+        for resource in requirements {
+            self.held.insert(resource, (id, behavior));
+        }
+        self.scheduled.insert(id);
+        self.incoming.push((id, command));
+
+        Ok(id)
+    }
+
+    pub fn cancel(&mut self, id: CommandId) {
+        if !self.scheduled.remove(&id) {
+            return;
+        }
+
+        self.release(id);
+
+        // Commands that never started don't need ending.
+        let before = self.incoming.len();
+        self.incoming.retain(|(incoming, _)| *incoming != id);
+        if self.incoming.len() == before {
+            self.cancelled.insert(id);
+        }
+    }
+
+    pub fn is_scheduled(&self, id: CommandId) -> bool {
+        self.scheduled.contains(&id)
+    }
+
+    pub fn holder(&self, resource: &Resource) -> Option<CommandId> {
+        self.held.get(resource).map(|&(holder, _)| holder)
+    }
+
+    pub fn set_default(
+        &mut self,
+        resource: Resource,
+        mut make: Factory,
+    ) -> Result<(), ScheduleError> {
+        let command = make();
+        if !command.requirements().contains(&resource) {
+            return Err(ScheduleError::DefaultRequirement {
+                resource: resource.to_string(),
+            });
+        }
+
+        self.defaults.insert(
+            resource,
+            DefaultCommand {
+                make,
+                next: Some(command),
+            },
+        );
+        Ok(())
+    }
+
+    /// Schedule the default command of every resource that isn't held, if
+    /// none of its other requirements are held either: a default command never
+    /// interrupts anything.
+    fn schedule_defaults(&mut self) {
+        let free: Vec<Resource> = self
+            .defaults
+            .keys()
+            .filter(|resource| !self.held.contains_key(resource))
+            .cloned()
+            .collect();
+
+        for resource in free {
+            let default = match self.defaults.get_mut(&resource) {
+                Some(default) => default,
+                None => continue,
+            };
+            let command = default.next.take().unwrap_or_else(|| (default.make)());
+
+            if command
+                .requirements()
+                .iter()
+                .any(|requirement| self.held.contains_key(requirement))
+            {
+                // Another default command may have claimed it this iteration.
+                if let Some(default) = self.defaults.get_mut(&resource) {
+                    default.next = Some(command);
+                }
+                continue;
+            }
+
+            let _ = self.schedule(command);
+        }
+    }
+
+    /// Unschedule a command that has ended on its own.
+    fn finish(&mut self, id: CommandId) {
+        self.scheduled.remove(&id);
+        self.release(id);
+    }
+
+    fn release(&mut self, id: CommandId) {
+        self.held.retain(|_, (holder, _)| *holder != id);
+    }
+
+    fn take_incoming(&mut self) -> Vec<(CommandId, Box<dyn Command>)> {
+        std::mem::take(&mut self.incoming)
+    }
+
+    fn take_cancelled(&mut self) -> HashSet<CommandId> {
+        std::mem::take(&mut self.cancelled)
+    }
+
+    pub fn bind(&mut self, trigger: Trigger) {
+        self.triggers.push(trigger);
+    }
+
+    fn take_triggers(&mut self) -> Vec<Trigger> {
+        std::mem::take(&mut self.triggers)
+    }
+
+    /// Put polled triggers back, ahead of any bound while they were polled.
+    fn restore_triggers(&mut self, mut triggers: Vec<Trigger>) {
+        triggers.append(&mut self.triggers);
+        self.triggers = triggers;
+    }
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    #[error("The command requires {resource}, which {holder} holds and won't give up.")]
+    #[diagnostic(
+        code(mekena::command::conflict),
+        help("The holder's `interruption` is `CancelIncoming`. Cancel it first, or wait for it to finish.")
+    )]
+    Conflict { resource: String, holder: CommandId },
+
+    #[error("The command scheduler has a period of zero.")]
+    #[diagnostic(
+        code(mekena::command::zero_period),
+        help("Commands can't be executed continuously. Give the scheduler a period of at least a millisecond.")
+    )]
+    ZeroPeriod,
+
+    #[error("The default command for {resource} doesn't require it.")]
+    #[diagnostic(
+        code(mekena::command::default_requirement),
+        help("List the resource in the command's `requirements`.")
+    )]
+    DefaultRequirement { resource: String },
+}
+
+impl fmt::Debug for DefaultCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DefaultCommand")
+            .field("next", &self.next)
+            .finish()
+    }
+}
@@ -0,0 +1,514 @@
+//! Commands built out of other commands.
+//!
+//! Groups run their children within a single scheduler iteration, and require
+//! every resource their children do, for as long as the group is scheduled. A
+//! group refuses to be interrupted if any of its children does.
+//!
+//! Most of these are easiest to build through [`CommandExt`], e.g.
+//! `drive.with_timeout(Duration::from_secs(3)).and_then(shoot)`.
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use super::{Command, InterruptionBehavior, Resource};
+use crate::context::Context;
+
+#[async_trait::async_trait(?Send)]
+impl Command for Box<dyn Command> {
+    fn requirements(&self) -> Vec<Resource> {
+        (**self).requirements()
+    }
+
+    fn interruption(&self) -> InterruptionBehavior {
+        (**self).interruption()
+    }
+
+    async fn initialize(&mut self, ctx: &Context) {
+        (**self).initialize(ctx).await
+    }
+
+    async fn execute(&mut self, ctx: &Context) {
+        (**self).execute(ctx).await
+    }
+
+    fn is_finished(&mut self, ctx: &Context) -> bool {
+        (**self).is_finished(ctx)
+    }
+
+    async fn end(&mut self, ctx: &Context, interrupted: bool) {
+        (**self).end(ctx, interrupted).await
+    }
+}
+
+/// Combinators for building commands out of other commands.
+pub trait CommandExt: Command + Sized + 'static {
+    /// Run `next` once this command finishes.
+    fn and_then(self, next: impl Command + 'static) -> Sequence {
+        Sequence::new([boxed(self), boxed(next)])
+    }
+
+    /// Run `other` at the same time, until both finish.
+    fn along_with(self, other: impl Command + 'static) -> Parallel {
+        Parallel::new([boxed(self), boxed(other)])
+    }
+
+    /// Run `other` at the same time, until either finishes.
+    fn race_with(self, other: impl Command + 'static) -> Race {
+        Race::new([boxed(self), boxed(other)])
+    }
+
+    /// Run `other` at the same time, until this command finishes.
+    fn deadline_with(self, other: impl Command + 'static) -> Deadline {
+        Deadline::new(self, [boxed(other)])
+    }
+
+    /// Interrupt this command if it hasn't finished within `timeout`.
+    fn with_timeout(self, timeout: Duration) -> WithTimeout {
+        WithTimeout {
+            inner: boxed(self),
+            timeout,
+            deadline: None,
+            finished: false,
+        }
+    }
+
+    /// Interrupt this command once `condition` holds. It is checked once per
+    /// scheduler iteration.
+    fn until(self, condition: impl FnMut(&Context) -> bool + 'static) -> Until {
+        Until {
+            inner: boxed(self),
+            condition: Box::new(condition),
+            finished: false,
+        }
+    }
+
+    /// Start this command again every time it finishes. It then only ends when
+    /// interrupted.
+    fn repeatedly(self) -> Repeatedly {
+        Repeatedly { inner: boxed(self) }
+    }
+}
+
+impl<C: Command + 'static> CommandExt for C {}
+
+/// A command that does nothing for `duration`.
+pub fn wait(duration: Duration) -> Wait {
+    Wait {
+        duration,
+        deadline: None,
+    }
+}
+
+fn boxed(command: impl Command + 'static) -> Box<dyn Command> {
+    Box::new(command)
+}
+
+fn requirements<'a>(commands: impl IntoIterator<Item = &'a Box<dyn Command>>) -> Vec<Resource> {
+    let mut requirements: Vec<Resource> = Vec::new();
+
+    for requirement in commands.into_iter().flat_map(|c| c.requirements()) {
+        if !requirements.contains(&requirement) {
+            requirements.push(requirement);
+        }
+    }
+
+    requirements
+}
+
+fn interruption<'a>(
+    mut commands: impl Iterator<Item = &'a Box<dyn Command>>,
+) -> InterruptionBehavior {
+    if commands.any(|c| c.interruption() == InterruptionBehavior::CancelIncoming) {
+        InterruptionBehavior::CancelIncoming
+    } else {
+        InterruptionBehavior::CancelSelf
+    }
+}
+
+/// Runs commands one after another. Finishes once the last one does.
+pub struct Sequence {
+    commands: Vec<Box<dyn Command>>,
+    current: usize,
+}
+
+impl Sequence {
+    pub fn new(commands: impl IntoIterator<Item = Box<dyn Command>>) -> Self {
+        Self {
+            commands: commands.into_iter().collect(),
+            current: 0,
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for Sequence {
+    fn requirements(&self) -> Vec<Resource> {
+        requirements(&self.commands)
+    }
+
+    fn interruption(&self) -> InterruptionBehavior {
+        interruption(self.commands.iter())
+    }
+
+    async fn initialize(&mut self, ctx: &Context) {
+        self.current = 0;
+
+        if let Some(first) = self.commands.first_mut() {
+            first.initialize(ctx).await;
+        }
+    }
+
+    async fn execute(&mut self, ctx: &Context) {
+        let command = match self.commands.get_mut(self.current) {
+            Some(command) => command,
+            None => return,
+        };
+
+        command.execute(ctx).await;
+
+        if command.is_finished(ctx) {
+            command.end(ctx, false).await;
+            self.current += 1;
+
+            if let Some(next) = self.commands.get_mut(self.current) {
+                next.initialize(ctx).await;
+            }
+        }
+    }
+
+    fn is_finished(&mut self, _ctx: &Context) -> bool {
+        self.current >= self.commands.len()
+    }
+
+    async fn end(&mut self, ctx: &Context, interrupted: bool) {
+        if let Some(command) = self.commands.get_mut(self.current) {
+            command.end(ctx, interrupted).await;
+        }
+    }
+}
+
+/// Commands being run at the same time, and whether each is still running.
+struct Group {
+    commands: Vec<(Box<dyn Command>, bool)>,
+}
+
+impl Group {
+    fn new(commands: impl IntoIterator<Item = Box<dyn Command>>) -> Self {
+        Self {
+            commands: commands.into_iter().map(|c| (c, false)).collect(),
+        }
+    }
+
+    fn requirements(&self) -> Vec<Resource> {
+        requirements(self.commands.iter().map(|(c, _)| c))
+    }
+
+    fn interruption(&self) -> InterruptionBehavior {
+        interruption(self.commands.iter().map(|(c, _)| c))
+    }
+
+    async fn initialize(&mut self, ctx: &Context) {
+        for (command, running) in &mut self.commands {
+            command.initialize(ctx).await;
+            *running = true;
+        }
+    }
+
+    /// Execute every running command, ending those that finish. Returns
+    /// whether any did.
+    async fn execute(&mut self, ctx: &Context) -> bool {
+        let mut any = false;
+
+        for (command, running) in self.commands.iter_mut().filter(|(_, r)| *r) {
+            command.execute(ctx).await;
+
+            if command.is_finished(ctx) {
+                command.end(ctx, false).await;
+                *running = false;
+                any = true;
+            }
+        }
+
+        any
+    }
+
+    fn all_finished(&self) -> bool {
+        self.commands.iter().all(|(_, running)| !running)
+    }
+
+    /// End every command still running, as interrupted.
+    async fn interrupt(&mut self, ctx: &Context) {
+        for (command, running) in self.commands.iter_mut().filter(|(_, r)| *r) {
+            command.end(ctx, true).await;
+            *running = false;
+        }
+    }
+}
+
+/// Runs commands at the same time. Finishes once all of them have.
+pub struct Parallel(Group);
+
+impl Parallel {
+    pub fn new(commands: impl IntoIterator<Item = Box<dyn Command>>) -> Self {
+        Self(Group::new(commands))
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for Parallel {
+    fn requirements(&self) -> Vec<Resource> {
+        self.0.requirements()
+    }
+
+    fn interruption(&self) -> InterruptionBehavior {
+        self.0.interruption()
+    }
+
+    async fn initialize(&mut self, ctx: &Context) {
+        self.0.initialize(ctx).await
+    }
+
+    async fn execute(&mut self, ctx: &Context) {
+        self.0.execute(ctx).await;
+    }
+
+    fn is_finished(&mut self, _ctx: &Context) -> bool {
+        self.0.all_finished()
+    }
+
+    async fn end(&mut self, ctx: &Context, _interrupted: bool) {
+        self.0.interrupt(ctx).await
+    }
+}
+
+/// Runs commands at the same time. Finishes once any of them has, and
+/// interrupts the rest.
+pub struct Race {
+    group: Group,
+    finished: bool,
+}
+
+impl Race {
+    pub fn new(commands: impl IntoIterator<Item = Box<dyn Command>>) -> Self {
+        Self {
+            group: Group::new(commands),
+            finished: false,
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for Race {
+    fn requirements(&self) -> Vec<Resource> {
+        self.group.requirements()
+    }
+
+    fn interruption(&self) -> InterruptionBehavior {
+        self.group.interruption()
+    }
+
+    async fn initialize(&mut self, ctx: &Context) {
+        self.finished = false;
+        self.group.initialize(ctx).await
+    }
+
+    async fn execute(&mut self, ctx: &Context) {
+        self.finished = self.group.execute(ctx).await;
+    }
+
+    fn is_finished(&mut self, _ctx: &Context) -> bool {
+        self.finished
+    }
+
+    async fn end(&mut self, ctx: &Context, _interrupted: bool) {
+        self.group.interrupt(ctx).await
+    }
+}
+
+/// Runs commands alongside a leader. Finishes once the leader has, and
+/// interrupts the rest.
+pub struct Deadline {
+    leader: Box<dyn Command>,
+    others: Group,
+    finished: bool,
+}
+
+impl Deadline {
+    pub fn new(
+        leader: impl Command + 'static,
+        others: impl IntoIterator<Item = Box<dyn Command>>,
+    ) -> Self {
+        Self {
+            leader: boxed(leader),
+            others: Group::new(others),
+            finished: false,
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for Deadline {
+    fn requirements(&self) -> Vec<Resource> {
+        let mut requirements = self.leader.requirements();
+        for requirement in self.others.requirements() {
+            if !requirements.contains(&requirement) {
+                requirements.push(requirement);
+            }
+        }
+
+        requirements
+    }
+
+    fn interruption(&self) -> InterruptionBehavior {
+        match self.leader.interruption() {
+            InterruptionBehavior::CancelSelf => self.others.interruption(),
+            incoming => incoming,
+        }
+    }
+
+    async fn initialize(&mut self, ctx: &Context) {
+        self.finished = false;
+        self.leader.initialize(ctx).await;
+        self.others.initialize(ctx).await;
+    }
+
+    async fn execute(&mut self, ctx: &Context) {
+        self.leader.execute(ctx).await;
+        self.finished = self.leader.is_finished(ctx);
+        self.others.execute(ctx).await;
+    }
+
+    fn is_finished(&mut self, _ctx: &Context) -> bool {
+        self.finished
+    }
+
+    async fn end(&mut self, ctx: &Context, interrupted: bool) {
+        self.leader.end(ctx, interrupted).await;
+        self.others.interrupt(ctx).await;
+    }
+}
+
+/// See [`CommandExt::with_timeout`].
+pub struct WithTimeout {
+    inner: Box<dyn Command>,
+    timeout: Duration,
+    deadline: Option<Instant>,
+    finished: bool,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for WithTimeout {
+    fn requirements(&self) -> Vec<Resource> {
+        self.inner.requirements()
+    }
+
+    fn interruption(&self) -> InterruptionBehavior {
+        self.inner.interruption()
+    }
+
+    async fn initialize(&mut self, ctx: &Context) {
+        self.deadline = Some(Instant::now() + self.timeout);
+        self.finished = false;
+        self.inner.initialize(ctx).await
+    }
+
+    async fn execute(&mut self, ctx: &Context) {
+        self.inner.execute(ctx).await;
+        self.finished = self.inner.is_finished(ctx);
+    }
+
+    fn is_finished(&mut self, _ctx: &Context) -> bool {
+        self.finished || self.deadline.map_or(false, |d| Instant::now() >= d)
+    }
+
+    async fn end(&mut self, ctx: &Context, interrupted: bool) {
+        self.inner.end(ctx, interrupted || !self.finished).await
+    }
+}
+
+/// See [`CommandExt::until`].
+pub struct Until {
+    inner: Box<dyn Command>,
+    condition: Box<dyn FnMut(&Context) -> bool>,
+    finished: bool,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for Until {
+    fn requirements(&self) -> Vec<Resource> {
+        self.inner.requirements()
+    }
+
+    fn interruption(&self) -> InterruptionBehavior {
+        self.inner.interruption()
+    }
+
+    async fn initialize(&mut self, ctx: &Context) {
+        self.finished = false;
+        self.inner.initialize(ctx).await
+    }
+
+    async fn execute(&mut self, ctx: &Context) {
+        self.inner.execute(ctx).await;
+        self.finished = self.inner.is_finished(ctx);
+    }
+
+    fn is_finished(&mut self, ctx: &Context) -> bool {
+        self.finished || (self.condition)(ctx)
+    }
+
+    async fn end(&mut self, ctx: &Context, interrupted: bool) {
+        self.inner.end(ctx, interrupted || !self.finished).await
+    }
+}
+
+/// See [`CommandExt::repeatedly`].
+pub struct Repeatedly {
+    inner: Box<dyn Command>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for Repeatedly {
+    fn requirements(&self) -> Vec<Resource> {
+        self.inner.requirements()
+    }
+
+    fn interruption(&self) -> InterruptionBehavior {
+        self.inner.interruption()
+    }
+
+    async fn initialize(&mut self, ctx: &Context) {
+        self.inner.initialize(ctx).await
+    }
+
+    async fn execute(&mut self, ctx: &Context) {
+        self.inner.execute(ctx).await;
+
+        if self.inner.is_finished(ctx) {
+            self.inner.end(ctx, false).await;
+            self.inner.initialize(ctx).await;
+        }
+    }
+
+    async fn end(&mut self, ctx: &Context, interrupted: bool) {
+        self.inner.end(ctx, interrupted).await
+    }
+}
+
+/// See [`wait`].
+pub struct Wait {
+    duration: Duration,
+    deadline: Option<Instant>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for Wait {
+    async fn initialize(&mut self, _ctx: &Context) {
+        self.deadline = Some(Instant::now() + self.duration);
+    }
+
+    fn is_finished(&mut self, _ctx: &Context) -> bool {
+        self.deadline.map_or(false, |d| Instant::now() >= d)
+    }
+}
@@ -0,0 +1,188 @@
+//! Triggers, which schedule and cancel commands as a condition changes.
+//!
+//! A [`Trigger`] wraps a condition, polled once per
+//! [`CommandScheduler`](super::CommandScheduler) iteration, e.g. whether a
+//! joystick button is held or a sensor reads past a threshold. Commands are
+//! bound to the condition becoming true or false, and the trigger is handed
+//! to the scheduler with [`Context::bind`](crate::context::Context::bind).
+//!
+//! Bindings take a function making the command, since a trigger can fire any
+//! number of times. Commands that can't be scheduled because their resources
+//! are held by a command that won't be interrupted are dropped.
+
+use std::{fmt, time::Duration};
+
+use mekena_messaging::prelude::Message;
+use tokio::time::Instant;
+
+use super::{factory, Command, CommandId, Factory, ScheduleError};
+use crate::context::Context;
+
+/// A condition that schedules commands. See the [module docs](self).
+pub struct Trigger {
+    condition: Box<dyn FnMut(&Context) -> bool>,
+    debounce: Option<Debounce>,
+    /// The condition's value at the last poll. Starts `false`, so a condition
+    /// that is already true when bound counts as becoming true.
+    last: bool,
+    bindings: Vec<Binding>,
+}
+
+struct Debounce {
+    duration: Duration,
+    /// Since when the condition has disagreed with `last`, if it does.
+    changing_since: Option<Instant>,
+}
+
+enum Binding {
+    OnTrue(Factory),
+    OnFalse(Factory),
+    WhileTrue(Factory, Option<CommandId>),
+    ToggleOnTrue(Factory, Option<CommandId>),
+}
+
+impl Trigger {
+    /// A trigger on a condition, polled once per scheduler iteration.
+    pub fn new(condition: impl FnMut(&Context) -> bool + 'static) -> Self {
+        Self {
+            condition: Box::new(condition),
+            debounce: None,
+            last: false,
+            bindings: Vec::new(),
+        }
+    }
+
+    /// A trigger that becomes true once for each message of type `M` sent to
+    /// the context's mailbox. It is true for one scheduler iteration per
+    /// message, and false for the iteration after, so messages arriving
+    /// together still fire one after another.
+    ///
+    /// The trigger only [taps](mekena_messaging::mailbox::Mailbox::tap) the
+    /// mailbox, so the messages are still delivered to the nodes receiving
+    /// them.
+    pub fn on_message<M: Message + 'static>(ctx: &Context) -> Self {
+        let tap = ctx.mailbox().tap::<M>();
+        let mut pending = 0;
+        let mut high = false;
+
+        Self::new(move |_| {
+            pending += tap.take();
+            high = !high && pending > 0;
+            if high {
+                pending -= 1;
+            }
+            high
+        })
+    }
+
+    /// Only treat the condition as changed once it has stayed changed for
+    /// `duration`, e.g. to ignore a bouncing switch.
+    pub fn debounce(mut self, duration: Duration) -> Self {
+        self.debounce = Some(Debounce {
+            duration,
+            changing_since: None,
+        });
+        self
+    }
+
+    /// Schedule a command each time the condition becomes true.
+    pub fn on_true<C: Command + 'static>(self, command: impl FnMut() -> C + 'static) -> Self {
+        self.bind(Binding::OnTrue(factory(command)))
+    }
+
+    /// Schedule a command each time the condition becomes false.
+    pub fn on_false<C: Command + 'static>(self, command: impl FnMut() -> C + 'static) -> Self {
+        self.bind(Binding::OnFalse(factory(command)))
+    }
+
+    /// Schedule a command when the condition becomes true, and cancel it when
+    /// the condition becomes false.
+    pub fn while_true<C: Command + 'static>(self, command: impl FnMut() -> C + 'static) -> Self {
+        self.bind(Binding::WhileTrue(factory(command), None))
+    }
+
+    /// Each time the condition becomes true, schedule a command if it isn't
+    /// scheduled, and cancel it if it is.
+    pub fn toggle_on_true<C: Command + 'static>(
+        self,
+        command: impl FnMut() -> C + 'static,
+    ) -> Self {
+        self.bind(Binding::ToggleOnTrue(factory(command), None))
+    }
+
+    fn bind(mut self, binding: Binding) -> Self {
+        self.bindings.push(binding);
+        self
+    }
+
+    /// Check the condition, and schedule or cancel commands if it changed.
+    pub(crate) fn poll(&mut self, ctx: &Context) {
+        let raw = (self.condition)(ctx);
+        let value = match &mut self.debounce {
+            Some(debounce) => debounce.filter(self.last, raw),
+            None => raw,
+        };
+
+        let rising = value && !self.last;
+        let falling = !value && self.last;
+        self.last = value;
+
+        for binding in &mut self.bindings {
+            match binding {
+                Binding::OnTrue(command) if rising => {
+                    let _ = schedule(ctx, command());
+                }
+                Binding::OnFalse(command) if falling => {
+                    let _ = schedule(ctx, command());
+                }
+                Binding::WhileTrue(command, scheduled) => {
+                    if rising {
+                        *scheduled = schedule(ctx, command()).ok();
+                    } else if falling {
+                        if let Some(id) = scheduled.take() {
+                            ctx.cancel_command(id);
+                        }
+                    }
+                }
+                Binding::ToggleOnTrue(command, scheduled) if rising => {
+                    match scheduled.take().filter(|&id| ctx.is_scheduled(id)) {
+                        Some(id) => ctx.cancel_command(id),
+                        None => *scheduled = schedule(ctx, command()).ok(),
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Debounce {
+    /// The debounced value, given the last one and the condition's.
+    fn filter(&mut self, last: bool, raw: bool) -> bool {
+        if raw == last {
+            self.changing_since = None;
+            return last;
+        }
+
+        let since = *self.changing_since.get_or_insert_with(Instant::now);
+        if since.elapsed() >= self.duration {
+            self.changing_since = None;
+            raw
+        } else {
+            last
+        }
+    }
+}
+
+fn schedule(ctx: &Context, command: Box<dyn Command>) -> Result<CommandId, ScheduleError> {
+    ctx.local().commands().schedule(command)
+}
+
+impl fmt::Debug for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Trigger")
+            .field("last", &self.last)
+            .field("bindings", &self.bindings.len())
+            .finish()
+    }
+}
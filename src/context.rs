@@ -1,73 +1,555 @@
-use std::any::Any;
+use std::{
+    any::TypeId,
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
-use dashmap::mapref::one::{Ref, RefMut};
+use futures::future::LocalBoxFuture;
 use mekena_messaging::{
     mailbox::Mailbox,
     prelude::{MailboxError, Message},
 };
-use mekena_state::StateManager;
-use mekena_util::shutdown::ShutdownManager;
+use mekena_state::{
+    Key, ResourceRef, ResourceRefMut, Resources, StateError, StateManager, StateRef, StateRefMut,
+};
+use mekena_util::shutdown::{ShutdownManager, ShutdownReason};
+use tokio::sync::{watch, Notify};
 
-#[derive(Debug)]
+use crate::{
+    command::{self, Command, CommandId, Resource, ScheduleError, Trigger},
+    event::EventStream,
+    job::{self, Job, JobError, JobInfo},
+    mode::Mode,
+    node::{Node, NodeHandle, NodeId, NodeOptions, NodeState},
+    periodic::TickStats,
+    registry::Registry,
+    system::SystemError,
+};
+
+/// A node's view of the system it runs in. Every node gets its own
+/// [`Context`], but they all share the same mailbox, state and shutdown signal.
+///
+/// Clones are views of the same node, for futures that can't borrow one.
+#[derive(Clone, Debug)]
 pub struct Context {
-    mailbox: Mailbox,
-    state: StateManager,
-    shutdown: ShutdownManager,
+    shared: Arc<Shared>,
+    local: Rc<Local>,
+    /// The whole system's shutdown signal.
+    system: ShutdownManager,
+    /// This node's shutdown signal, a descendant of the current run cycle's,
+    /// which is in turn a descendant of `system`.
+    scope: ShutdownManager,
+    node: Option<NodeId>,
+}
+
+/// The parts of a [`Context`] that can be shared across threads.
+#[derive(Debug, Default)]
+pub(crate) struct Shared {
+    pub(crate) mailbox: Mailbox,
+    pub(crate) state: StateManager,
+    /// Shared with the enclosing system, unlike the state.
+    pub(crate) resources: Arc<Resources>,
+    pub(crate) registry: Arc<Registry>,
+    next_id: Arc<AtomicU64>,
+    /// The enclosing system's, for the children of a
+    /// [`Subsystem`](crate::subsystem::Subsystem).
+    parent: Option<Arc<Shared>>,
+}
+
+/// The parts of a [`Context`] that can't leave the system's task, since nodes
+/// aren't required to be [`Send`].
+#[derive(Default)]
+pub(crate) struct Local {
+    spawned: RefCell<Vec<LocalBoxFuture<'static, ()>>>,
+    notify: Notify,
+    /// Why composite nodes failed, until the hook that failed them returns.
+    /// See [`Context::fail`].
+    failures: RefCell<HashMap<NodeId, SystemError>>,
+    /// Commands aren't [`Send`] either.
+    commands: RefCell<command::Queue>,
+    /// Nor are jobs.
+    jobs: RefCell<job::Table>,
 }
 
 impl Context {
     pub fn new() -> Self {
+        let system = ShutdownManager::new();
+
+        Self {
+            shared: Arc::default(),
+            local: Rc::default(),
+            scope: system.clone(),
+            system,
+            node: None,
+        }
+    }
+
+    /// Create the context for a new node, whose lifetime is scoped to this
+    /// context's.
+    pub(crate) fn for_node(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            local: self.local.clone(),
+            system: self.system.clone(),
+            scope: self.scope.child(),
+            node: Some(NodeId(self.shared.next_id.fetch_add(1, Ordering::Relaxed))),
+        }
+    }
+
+    /// Create a context with its own mailbox and state, for the children of a
+    /// [`Subsystem`](crate::subsystem::Subsystem). Everything else is shared
+    /// with this context.
+    pub(crate) fn scoped(&self) -> Self {
         Self {
-            mailbox: Mailbox::new(),
-            state: StateManager::new(),
-            shutdown: ShutdownManager::new(),
+            shared: Arc::new(Shared {
+                mailbox: Mailbox::default(),
+                state: StateManager::default(),
+                resources: self.shared.resources.clone(),
+                registry: self.shared.registry.clone(),
+                next_id: self.shared.next_id.clone(),
+                parent: Some(self.shared.clone()),
+            }),
+            local: self.local.clone(),
+            system: self.system.clone(),
+            scope: self.scope.clone(),
+            node: None,
         }
     }
 
+    /// Scope this context under `parent`, e.g. a new run cycle's shutdown
+    /// signal.
+    pub(crate) fn rescope(&mut self, parent: &ShutdownManager) {
+        self.scope = match self.node {
+            Some(_) => parent.child(),
+            None => parent.clone(),
+        };
+    }
+
+    /// The ID of the node this context belongs to, if any.
+    pub fn node_id(&self) -> Option<NodeId> {
+        self.node
+    }
+
     /// Send any message: [`Message`] to the mailbox.
     pub async fn send<M: Message + 'static>(&self, message: M) -> Result<(), ContextError> {
-        self.mailbox.send(message).await.map_err(ContextError::from)
+        self.shared
+            .mailbox
+            .send(message)
+            .await
+            .map_err(ContextError::from)
+    }
+
+    /// Send any message: [`Message`] to the mailbox of the system enclosing
+    /// this node's [`Subsystem`](crate::subsystem::Subsystem). For nodes that
+    /// aren't in a subsystem, this is the same as [`Context::send`].
+    pub async fn send_outer<M: Message + 'static>(&self, message: M) -> Result<(), ContextError> {
+        self.shared
+            .parent
+            .as_ref()
+            .unwrap_or(&self.shared)
+            .mailbox
+            .send(message)
+            .await
+            .map_err(ContextError::from)
     }
 
     /// Asynchronously wait for a new message with type M: [`Message`].
     pub async fn recv<M: Message + 'static>(&self) -> Result<Box<M>, ContextError> {
-        self.mailbox.recv::<M>().await.map_err(ContextError::from)
+        self.shared
+            .mailbox
+            .recv::<M>()
+            .await
+            .map_err(ContextError::from)
+    }
+
+    /// Add a node to the running system. It goes through its own starting,
+    /// running and stopping hooks, independently of the system's phases.
+    ///
+    /// The new node is scoped to this one: it is stopped when this node is,
+    /// or when the whole system shuts down, whichever comes first. Use the
+    /// returned [`NodeHandle`] to stop it earlier, or to wait for it.
+    pub fn spawn_node<N: Node + 'static>(&self, node: N) -> NodeHandle {
+        let ctx = self.for_node();
+        let id = ctx.node.expect("node contexts always have an ID");
+        let (done, finished) = watch::channel(false);
+        let handle = NodeHandle::new(
+            id,
+            std::any::type_name::<N>(),
+            self.shared.clone(),
+            ctx.scope.clone(),
+            finished,
+        );
+
+        self.shared.registry.register(
+            id,
+            std::any::type_name::<N>().to_owned(),
+            TypeId::of::<N>(),
+            NodeOptions::default(),
+        );
+
+        self.local.spawn(Box::pin(crate::system::lifecycle(
+            Box::new(node),
+            ctx,
+            done,
+        )));
+
+        handle
+    }
+
+    /// Mark this node as ready. Nodes are ready once their `starting` hook
+    /// returns, unless they were registered with
+    /// [`NodeConfig::manual_ready`](crate::node::NodeConfig::manual_ready), in
+    /// which case they must call this themselves.
+    pub fn ready(&self) {
+        if let Some(id) = self.node {
+            self.shared.registry.set_ready(id);
+        }
+    }
+
+    /// Returns `true` if this node has been marked as ready.
+    pub fn is_ready(&self) -> bool {
+        self.node
+            .map_or(false, |id| self.shared.registry.is_ready(id))
+    }
+
+    /// Wait until there is at least one node of type `N`, and every node of
+    /// that type is ready.
+    pub async fn wait_ready<N: Node + 'static>(&self) {
+        self.shared
+            .registry
+            .wait_ready_matching(|record| record.type_id == TypeId::of::<N>())
+            .await
+    }
+
+    /// Wait until there is a node with the given name, and it is ready.
+    pub async fn wait_ready_named(&self, name: &str) {
+        self.shared
+            .registry
+            .wait_ready_matching(|record| record.name == name)
+            .await
+    }
+
+    /// Ask a node to pause. Its `running` hook is cancelled at its next
+    /// `.await`, then its [`Node::pause`] hook is called. The node stays paused
+    /// until it is resumed with [`Context::resume_node`], or the system shuts
+    /// down.
+    ///
+    /// A node that isn't running yet pauses as soon as it starts running.
+    /// Returns `false` if there is no such node.
+    pub fn pause_node(&self, id: NodeId) -> bool {
+        self.shared.registry.request_pause(id, true)
+    }
+
+    /// Ask a paused node to resume. Its [`Node::resume`] hook is called, then
+    /// its `running` hook is called again from the start. Returns `false` if
+    /// there is no such node.
+    pub fn resume_node(&self, id: NodeId) -> bool {
+        self.shared.registry.request_pause(id, false)
+    }
+
+    /// The phase a node is currently in, if there is such a node.
+    pub fn node_state(&self, id: NodeId) -> Option<NodeState> {
+        self.shared.registry.state(id)
+    }
+
+    /// Schedule a command, to be run by the system's
+    /// [`CommandScheduler`](crate::command::CommandScheduler). Any scheduled
+    /// command holding a resource this one requires is interrupted, unless it
+    /// refuses to be, in which case this command is rejected instead. See
+    /// [`crate::command`].
+    pub fn schedule<C: Command + 'static>(&self, command: C) -> Result<CommandId, ScheduleError> {
+        self.local.commands().schedule(Box::new(command))
+    }
+
+    /// Cancel a scheduled command. Its `end` hook is called with
+    /// `interrupted` set, if it had been initialized.
+    pub fn cancel_command(&self, id: CommandId) {
+        self.local.commands().cancel(id)
+    }
+
+    /// Returns `true` if the command is scheduled, and hasn't ended or been
+    /// cancelled.
+    pub fn is_scheduled(&self, id: CommandId) -> bool {
+        self.local.commands().is_scheduled(id)
+    }
+
+    /// Set the command scheduled whenever no other command holds `resource`,
+    /// and so resumed after a command interrupting it ends. It must require
+    /// the resource. A default command replaced while scheduled runs until it
+    /// ends or is interrupted.
+    pub fn set_default_command<C: Command + 'static>(
+        &self,
+        resource: Resource,
+        command: impl FnMut() -> C + 'static,
+    ) -> Result<(), ScheduleError> {
+        self.local
+            .commands()
+            .set_default(resource, command::factory(command))
+    }
+
+    /// The command holding a resource, which may be its default command.
+    pub fn holder(&self, resource: &Resource) -> Option<CommandId> {
+        self.local.commands().holder(resource)
+    }
+
+    /// Hand a trigger to the
+    /// [`CommandScheduler`](crate::command::CommandScheduler), which polls it
+    /// from then on. See [`crate::command::trigger`].
+    pub fn bind(&self, trigger: Trigger) {
+        self.local.commands().bind(trigger)
+    }
+
+    /// Add a job, to be run by the [`JobScheduler`](crate::job::JobScheduler).
+    /// See [`crate::job`].
+    pub fn add_job(&self, job: Job) -> Result<(), JobError> {
+        self.local.jobs().add(job)
+    }
+
+    /// Remove a job by name. Returns `false` if there was no such job.
+    pub fn remove_job(&self, name: &str) -> bool {
+        self.local.jobs().remove(name)
+    }
+
+    /// Every job, and when it next runs.
+    pub fn jobs(&self) -> Vec<JobInfo> {
+        self.local.jobs().infos()
+    }
+
+    /// The mode the system is in. See [`crate::mode`].
+    pub fn mode(&self) -> Mode {
+        self.shared.registry.mode()
+    }
+
+    /// Ask the system to switch to another mode. Every node leaves the current
+    /// mode and enters the new one. See [`crate::mode`].
+    pub fn request_mode(&self, mode: Mode) {
+        self.shared.registry.set_mode(mode)
+    }
+
+    /// Tell this node's watchdog that it is alive. See [`crate::watchdog`].
+    pub fn heartbeat(&self) {
+        if let Some(id) = self.node {
+            self.shared.registry.heartbeat(id);
+        }
+    }
+
+    /// How closely this node has kept to its schedule, if it is periodic. See
+    /// [`crate::periodic`].
+    pub fn tick_stats(&self) -> Option<TickStats> {
+        self.shared.registry.info(self.node?)?.tick_stats()
+    }
+
+    /// Subscribe to the system's [`LifecycleEvent`](crate::event::LifecycleEvent)s.
+    pub fn events(&self) -> EventStream {
+        self.shared.registry.subscribe()
     }
 
     /// Inserts a key and a value into the map. Returns the old value associated
-    /// with the key if there was one.
+    /// with the key if there was one. If the old value is of another type, it
+    /// is kept, and an error is returned instead.
     ///
     /// **Locking behaviour: May deadlock if called when holding any sort of
     /// reference into the map.**. Unfortunately, this is inherited from
     /// [`dashmap`].
-    pub fn insert<V: 'static + Send + Sync>(&self, key: String, value: V) -> Option<Box<V>> {
-        self.state.insert(key, value)
+    pub fn insert<V: 'static + Send + Sync, K: Key<V> + ?Sized>(
+        &self,
+        key: &K,
+        value: V,
+    ) -> Result<Option<Box<V>>, ContextError> {
+        self.shared
+            .state
+            .insert(key, value)
+            .map_err(ContextError::from)
     }
 
-    /// Get a immutable reference to an entry in the map
+    /// Get an immutable reference to a value of type `V` in the map. The key
+    /// is a [`StateKey`](mekena_state::StateKey), or a string.
     ///
     /// **Locking behaviour: May deadlock if called when holding a mutable
     /// reference into the map.** Unfortunately, this is inherited from
     /// [`dashmap`].
-    pub fn get(&self, key: String) -> Option<Ref<String, Box<dyn Any + Send + Sync>>> {
-        self.state.get(key)
+    pub fn get<V: 'static + Send + Sync, K: Key<V> + ?Sized>(
+        &self,
+        key: &K,
+    ) -> Result<Option<StateRef<'_, V>>, ContextError> {
+        self.shared.state.get(key).map_err(ContextError::from)
     }
 
-    /// Get a mutable reference to an entry in the map
+    /// Get a mutable reference to a value of type `V` in the map.
     ///
     /// **Locking behaviour: May deadlock if called when holding any sort of
     /// reference into the map.** Unfortunately, this is inherited from
     /// [`dashmap`].
-    pub fn get_mut(&self, key: String) -> Option<RefMut<String, Box<dyn Any + Send + Sync>>> {
-        self.state.get_mut(key)
+    pub fn get_mut<V: 'static + Send + Sync, K: Key<V> + ?Sized>(
+        &self,
+        key: &K,
+    ) -> Result<Option<StateRefMut<'_, V>>, ContextError> {
+        self.shared.state.get_mut(key).map_err(ContextError::from)
     }
 
+    /// The resource of type `T`, added with
+    /// [`System::add_state`](crate::system::System::add_state).
+    ///
+    /// **Locking behaviour: May deadlock if called when holding a mutable
+    /// reference into the map, to any resource.** Unfortunately, this is
+    /// inherited from [`dashmap`], which locks whole shards rather than
+    /// single entries.
+    pub fn resource<T: 'static + Send + Sync>(&self) -> Result<ResourceRef<'_, T>, ContextError> {
+        self.shared
+            .resources
+            .get::<T>()
+            .ok_or_else(ContextError::missing_resource::<T>)
+    }
+
+    /// A mutable reference to the resource of type `T`, added with
+    /// [`System::add_state`](crate::system::System::add_state).
+    ///
+    /// **Locking behaviour: May deadlock if called when holding any sort of
+    /// reference into the map, to any resource.** Unfortunately, this is
+    /// inherited from [`dashmap`], which locks whole shards rather than
+    /// single entries.
+    pub fn resource_mut<T: 'static + Send + Sync>(
+        &self,
+    ) -> Result<ResourceRefMut<'_, T>, ContextError> {
+        self.shared
+            .resources
+            .get_mut::<T>()
+            .ok_or_else(ContextError::missing_resource::<T>)
+    }
+
+    /// The system's current run cycle, counting from 1. See
+    /// [`System::start`](crate::system::System::start).
+    pub fn cycle(&self) -> u64 {
+        self.shared.registry.cycle_number()
+    }
+
+    /// End the current run cycle with [`ShutdownReason::Requested`]. Every
+    /// node is stopped as if the system was shutting down, but the system can
+    /// be started again afterwards.
+    pub fn end_cycle(&self) {
+        self.shared.registry.end_cycle(ShutdownReason::Requested)
+    }
+
+    /// Signal the system to shut down with [`ShutdownReason::Requested`].
+    /// Every node waiting on [`Context::await_shutdown`] is woken. Calling this
+    /// more than once has no further effect.
+    ///
+    /// Unlike [`Context::end_cycle`], this is final: the system can't be
+    /// started again.
     pub async fn shutdown(&self) {
-        self.shutdown.shutdown().await
+        self.system.shutdown()
+    }
+
+    /// Signal the system to shut down for the given reason. Only the first
+    /// reason given is kept.
+    pub fn shutdown_with(&self, reason: ShutdownReason) {
+        self.system.shutdown_with(reason)
+    }
+
+    /// The reason this node is shutting down, if it is. Useful in
+    /// [`Node::stopping`](crate::node::Node::stopping) to tell a clean stop
+    /// from a fault.
+    pub fn shutdown_reason(&self) -> Option<ShutdownReason> {
+        self.scope.reason()
     }
 
+    /// Returns `true` if this node has been signalled to shut down, either on
+    /// its own or as part of the whole system.
+    pub fn is_shutdown(&self) -> bool {
+        self.scope.is_shutdown()
+    }
+
+    /// Wait until this node is signalled to shut down, either on its own or as
+    /// part of the whole system.
     pub async fn await_shutdown(&self) {
-        self.shutdown.await_shutdown().await
+        self.scope.await_shutdown().await
+    }
+
+    pub(crate) fn shutdown_manager(&self) -> ShutdownManager {
+        self.system.clone()
+    }
+
+    pub(crate) fn scope(&self) -> &ShutdownManager {
+        &self.scope
+    }
+
+    pub(crate) fn mailbox(&self) -> &Mailbox {
+        &self.shared.mailbox
+    }
+
+    pub(crate) fn shared(&self) -> &Arc<Shared> {
+        &self.shared
+    }
+
+    pub(crate) fn registry(&self) -> &Registry {
+        &self.shared.registry
+    }
+
+    pub(crate) fn local(&self) -> &Local {
+        &self.local
+    }
+
+    /// Fail this node with `error` once the hook it is in returns, as if the
+    /// hook had panicked. For composite nodes, whose children's errors are
+    /// their own.
+    pub(crate) fn fail(&self, error: SystemError) {
+        let id = self.node.expect("only nodes fail");
+        self.local.failures.borrow_mut().insert(id, error);
+    }
+
+    /// Take the error this node was failed with, if it was.
+    pub(crate) fn take_failure(&self) -> Option<SystemError> {
+        self.local.failures.borrow_mut().remove(&self.node?)
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Local {
+    fn spawn(&self, future: LocalBoxFuture<'static, ()>) {
+        self.spawned.borrow_mut().push(future);
+        self.notify.notify_one();
+    }
+
+    /// Take every future spawned since the last call.
+    pub(crate) fn take_spawned(&self) -> Vec<LocalBoxFuture<'static, ()>> {
+        std::mem::take(&mut *self.spawned.borrow_mut())
+    }
+
+    /// Wait until something is spawned.
+    pub(crate) async fn notified(&self) {
+        self.notify.notified().await
+    }
+
+    /// The system's commands. Don't hold on to this across an `.await`.
+    pub(crate) fn commands(&self) -> std::cell::RefMut<'_, command::Queue> {
+        self.commands.borrow_mut()
+    }
+
+    /// The system's jobs. Don't hold on to this across an `.await`.
+    pub(crate) fn jobs(&self) -> std::cell::RefMut<'_, job::Table> {
+        self.jobs.borrow_mut()
+    }
+}
+
+impl fmt::Debug for Local {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Local")
+            .field("spawned", &self.spawned.borrow().len())
+            .field("failures", &self.failures)
+            .field("commands", &self.commands)
+            .field("jobs", &self.jobs)
+            .finish()
     }
 }
 
@@ -75,4 +557,20 @@ impl Context {
 pub enum ContextError {
     #[error(transparent)]
     MailboxError(#[from] MailboxError),
+    #[error(transparent)]
+    StateError(#[from] StateError),
+    #[error("There is no resource of type {type_name}.")]
+    #[diagnostic(
+        code(mekena::context::missing_resource),
+        help("Add it to the system with `System::add_state`.")
+    )]
+    MissingResource { type_name: &'static str },
+}
+
+impl ContextError {
+    fn missing_resource<T>() -> Self {
+        Self::MissingResource {
+            type_name: std::any::type_name::<T>(),
+        }
+    }
 }
@@ -0,0 +1,138 @@
+//! Ordering nodes by their declared dependencies.
+
+use std::any::TypeId;
+
+use crate::{node::Dependency, system::SystemError};
+
+/// What the ordering needs to know about a registered node.
+pub(crate) struct Declaration<'a> {
+    pub name: &'a str,
+    pub type_id: TypeId,
+    pub dependencies: &'a [Dependency],
+}
+
+/// The result of ordering nodes. All indices point into the declarations that
+/// were ordered.
+#[derive(Debug, Default)]
+pub(crate) struct Ordering {
+    /// Nodes grouped such that every node's dependencies are in an earlier
+    /// level. Nodes within a level don't depend on each other.
+    pub levels: Vec<Vec<usize>>,
+    /// The nodes each node depends on.
+    pub dependencies: Vec<Vec<usize>>,
+}
+
+/// Sort nodes into levels, such that every node's dependencies are in an
+/// earlier level. Nodes within a level don't depend on each other, so they can
+/// start in parallel.
+pub(crate) fn order(nodes: &[Declaration]) -> Result<Ordering, SystemError> {
+    // edges[i] holds the indices that node i depends on.
+    let edges = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| resolve(nodes, i, node))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut remaining: Vec<usize> = edges.iter().map(Vec::len).collect();
+    let mut placed = vec![false; nodes.len()];
+    let mut levels = Vec::new();
+
+    loop {
+        let level: Vec<usize> = (0..nodes.len())
+            .filter(|&i| !placed[i] && remaining[i] == 0)
+            .collect();
+
+        if level.is_empty() {
+            break;
+        }
+
+        for &i in &level {
+            placed[i] = true;
+        }
+
+        for (i, dependencies) in edges.iter().enumerate() {
+            remaining[i] -= dependencies.iter().filter(|d| level.contains(d)).count();
+        }
+
+        levels.push(level);
+    }
+
+    match placed.iter().position(|placed| !placed) {
+        Some(start) => Err(SystemError::DependencyCycle {
+            cycle: cycle(nodes, &edges, &placed, start),
+        }),
+        None => Ok(Ordering {
+            levels,
+            dependencies: edges,
+        }),
+    }
+}
+
+/// Resolve a node's dependencies to the indices of the nodes they refer to.
+fn resolve(
+    nodes: &[Declaration],
+    index: usize,
+    node: &Declaration,
+) -> Result<Vec<usize>, SystemError> {
+    let mut resolved = Vec::new();
+
+    for dependency in node.dependencies {
+        let matches: Vec<usize> = nodes
+            .iter()
+            .enumerate()
+            .filter(|&(i, other)| {
+                i != index
+                    && match dependency {
+                        Dependency::Type(type_id, _) => other.type_id == *type_id,
+                        Dependency::Name(name) => other.name == name,
+                    }
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        if matches.is_empty() {
+            return Err(SystemError::MissingDependency {
+                node: node.name.to_owned(),
+                dependency: dependency.to_string(),
+            });
+        }
+
+        resolved.extend(matches);
+    }
+
+    resolved.sort_unstable();
+    resolved.dedup();
+
+    Ok(resolved)
+}
+
+/// Walk dependencies from an unplaced node until we come back around, and
+/// render the cycle we found as `a -> b -> a`.
+fn cycle(nodes: &[Declaration], edges: &[Vec<usize>], placed: &[bool], start: usize) -> String {
+    let mut path = vec![start];
+    let mut current = start;
+
+    // Every unplaced node has at least one unplaced dependency, otherwise it
+    // would have been placed. So this walk never gets stuck, and must
+    // eventually revisit a node.
+    let start = loop {
+        current = edges[current]
+            .iter()
+            .copied()
+            .find(|&d| !placed[d])
+            .expect("unplaced nodes have an unplaced dependency");
+
+        if let Some(position) = path.iter().position(|&i| i == current) {
+            break position;
+        }
+
+        path.push(current);
+    };
+
+    path[start..]
+        .iter()
+        .chain(std::iter::once(&current))
+        .map(|&i| nodes[i].name)
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
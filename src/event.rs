@@ -0,0 +1,81 @@
+//! Lifecycle events, broadcast as the system and its nodes change phase.
+//!
+//! Subscribe with [`Context::events`](crate::context::Context::events) from
+//! inside a node, or with [`SystemHandle::events`](crate::handle::SystemHandle::events)
+//! from outside the system.
+
+use tokio::sync::broadcast;
+
+use crate::{mode::Mode, node::NodeId, system::SystemState, watchdog::Miss};
+
+/// How many events a slow subscriber can fall behind by before it starts
+/// missing them.
+pub(crate) const CAPACITY: usize = 256;
+
+/// Something that happened to the system, or to one of its nodes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    /// The system entered a new phase.
+    System(SystemState),
+    /// The system switched to another mode.
+    Mode(Mode),
+    /// The process received SIGHUP, with reloading enabled. See
+    /// [`crate::signal`].
+    Reload,
+    /// A node changed phase.
+    Node {
+        id: NodeId,
+        name: String,
+        event: NodeEvent,
+    },
+}
+
+/// Something that happened to a node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NodeEvent {
+    /// The node's `starting` hook returned.
+    Started,
+    /// The node became ready.
+    Ready,
+    /// The node's `running` hook began.
+    Running,
+    /// The node was paused, and its `pause` hook returned.
+    Paused,
+    /// The node was resumed, and its `resume` hook returned.
+    Resumed,
+    /// The node missed a deadline. See [`crate::watchdog`].
+    DeadlineMissed(Miss),
+    /// The node's `stopping` hook returned.
+    Stopped,
+    /// One of the node's hooks panicked, with the given message.
+    Failed(String),
+    /// The node was started again after stopping or failing.
+    Restarted,
+}
+
+/// A stream of [`LifecycleEvent`]s. Only events sent after the stream was
+/// created are received.
+#[derive(Debug)]
+pub struct EventStream {
+    receiver: broadcast::Receiver<LifecycleEvent>,
+}
+
+impl EventStream {
+    pub(crate) fn new(receiver: broadcast::Receiver<LifecycleEvent>) -> Self {
+        Self { receiver }
+    }
+
+    /// Wait for the next event. Returns [`None`] once the system is gone.
+    ///
+    /// If this stream falls too far behind, the oldest events it hasn't seen
+    /// are skipped.
+    pub async fn next(&mut self) -> Option<LifecycleEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
@@ -0,0 +1,305 @@
+//! Finite state machines, for nodes whose behaviour is a set of states and the
+//! messages that move between them.
+//!
+//! A [`StateMachine`] is built from its initial state, the transitions between
+//! states, each taken when a message of some type arrives, optionally guarded
+//! by a check on the message, and actions run on entering and exiting states.
+//! A node drives it from its `running` hook with [`StateMachine::step`], or
+//! feeds it messages it received itself with [`StateMachine::handle`].
+//!
+//! Actions are async, and get their own clone of the context, so they can send
+//! messages. Transitions are taken on messages that are `Clone`, as
+//! [`StateMachine::step`] works on copies of them.
+//!
+//! The most recent transitions taken are logged, and the machine can be
+//! exported as a Graphviz graph with [`StateMachine::to_dot`].
+
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::VecDeque,
+    fmt::{self, Write},
+};
+
+use futures::{future::LocalBoxFuture, Future, FutureExt};
+use mekena_messaging::prelude::{Copies, Message};
+use tokio::time::Instant;
+
+use crate::context::{Context, ContextError};
+
+/// A state of a [`StateMachine`], usually a fieldless enum. Implemented for
+/// every type that fits.
+pub trait State: Clone + PartialEq + fmt::Debug + 'static {}
+
+impl<S: Clone + PartialEq + fmt::Debug + 'static> State for S {}
+
+/// How many transitions are logged by default.
+const LOG_CAPACITY: usize = 64;
+
+type Action = Box<dyn FnMut(Context) -> LocalBoxFuture<'static, ()>>;
+type Guard = Box<dyn FnMut(&dyn Any) -> bool>;
+
+/// A state machine. See the [module docs](self).
+pub struct StateMachine<S: State> {
+    state: S,
+    transitions: Vec<Transition<S>>,
+    entry: Vec<(S, Action)>,
+    exit: Vec<(S, Action)>,
+    /// The most recent transitions, oldest first, up to `log_capacity`.
+    log: VecDeque<TransitionRecord<S>>,
+    log_capacity: usize,
+    /// Copies of the messages the transitions are taken on, once
+    /// [`StateMachine::step`] was first called.
+    copies: Option<Copies>,
+}
+
+struct Transition<S> {
+    from: S,
+    to: S,
+    message: TypeId,
+    message_name: &'static str,
+    /// Copy the transition's messages for [`StateMachine::step`].
+    copy: fn(&Copies),
+    guard: Option<Guard>,
+    /// How many times the transition was taken.
+    taken: u64,
+}
+
+/// A transition a [`StateMachine`] took.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransitionRecord<S> {
+    pub from: S,
+    pub to: S,
+    /// The type name of the message that triggered the transition.
+    pub message: &'static str,
+    pub at: Instant,
+}
+
+impl<S: State> StateMachine<S> {
+    /// A state machine in its initial state. That state's entry actions aren't
+    /// run.
+    pub fn new(initial: S) -> Self {
+        Self {
+            state: initial,
+            transitions: Vec::new(),
+            entry: Vec::new(),
+            exit: Vec::new(),
+            log: VecDeque::new(),
+            log_capacity: LOG_CAPACITY,
+            copies: None,
+        }
+    }
+
+    /// Log only the last `capacity` transitions, rather than the last 64.
+    /// With 0, nothing is logged.
+    pub fn log_capacity(mut self, capacity: usize) -> Self {
+        self.log_capacity = capacity;
+        let excess = self.log.len().saturating_sub(capacity);
+        self.log.drain(..excess);
+        self
+    }
+
+    /// Move from `from` to `to` when a message of type `M` arrives.
+    pub fn transition<M: Message + Clone + 'static>(self, from: S, to: S) -> Self {
+        self.add_transition::<M>(from, to, None)
+    }
+
+    /// Move from `from` to `to` when a message of type `M` arrives, if `guard`
+    /// accepts it.
+    pub fn guarded<M: Message + Clone + 'static>(
+        self,
+        from: S,
+        to: S,
+        mut guard: impl FnMut(&M) -> bool + 'static,
+    ) -> Self {
+        self.add_transition::<M>(
+            from,
+            to,
+            Some(Box::new(move |message| {
+                message.downcast_ref::<M>().map_or(false, &mut guard)
+            })),
+        )
+    }
+
+    fn add_transition<M: Message + Clone + 'static>(
+        mut self,
+        from: S,
+        to: S,
+        guard: Option<Guard>,
+    ) -> Self {
+        self.transitions.push(Transition {
+            from,
+            to,
+            message: TypeId::of::<M>(),
+            message_name: type_name::<M>(),
+            copy: Copies::add::<M>,
+            guard,
+            taken: 0,
+        });
+        self
+    }
+
+    /// Run `action` whenever the machine enters `state`.
+    pub fn on_enter<F: Future<Output = ()> + 'static>(
+        mut self,
+        state: S,
+        action: impl FnMut(Context) -> F + 'static,
+    ) -> Self {
+        self.entry.push((state, action_fn(action)));
+        self
+    }
+
+    /// Run `action` whenever the machine leaves `state`.
+    pub fn on_exit<F: Future<Output = ()> + 'static>(
+        mut self,
+        state: S,
+        action: impl FnMut(Context) -> F + 'static,
+    ) -> Self {
+        self.exit.push((state, action_fn(action)));
+        self
+    }
+
+    /// The current state.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// The most recent transitions taken, oldest first. See
+    /// [`Self::log_capacity`].
+    pub fn log(&self) -> impl Iterator<Item = &TransitionRecord<S>> {
+        self.log.iter()
+    }
+
+    /// Wait for the next message of a type some transition is taken on, and
+    /// take the first transition out of the current state it triggers, if
+    /// any.
+    ///
+    /// The machine only sees copies of the messages, so they are still
+    /// delivered to the nodes receiving them. It sees those sent from the
+    /// first call on.
+    pub async fn step(
+        &mut self,
+        ctx: &Context,
+    ) -> Result<Option<TransitionRecord<S>>, ContextError> {
+        let transitions = &self.transitions;
+        let copies = self.copies.get_or_insert_with(|| {
+            let copies = ctx.mailbox().copies();
+            for transition in transitions {
+                (transition.copy)(&copies);
+            }
+            copies
+        });
+
+        let message = copies.recv().await?;
+        Ok(self.dispatch(ctx, &*message).await)
+    }
+
+    /// Take the first transition out of the current state `message` triggers,
+    /// if any. Takes the message as [`Context::recv`] returns it.
+    pub async fn handle<M: Message + 'static>(
+        &mut self,
+        ctx: &Context,
+        message: Box<M>,
+    ) -> Option<TransitionRecord<S>> {
+        self.dispatch(ctx, &*message).await
+    }
+
+    async fn dispatch(&mut self, ctx: &Context, message: &dyn Any) -> Option<TransitionRecord<S>> {
+        let message_type = message.type_id();
+        let index = self.transitions.iter_mut().position(|transition| {
+            transition.from == self.state
+                && transition.message == message_type
+                && transition
+                    .guard
+                    .as_mut()
+                    .map_or(true, |guard| guard(message))
+        })?;
+        let transition = &mut self.transitions[index];
+        transition.taken += 1;
+
+        let record = TransitionRecord {
+            from: transition.from.clone(),
+            to: transition.to.clone(),
+            message: transition.message_name,
+            at: Instant::now(),
+        };
+
+        for (_, action) in self
+            .exit
+            .iter_mut()
+            .filter(|(state, _)| *state == record.from)
+        {
+            action(ctx.clone()).await;
+        }
+        self.state = record.to.clone();
+        for (_, action) in self
+            .entry
+            .iter_mut()
+            .filter(|(state, _)| *state == record.to)
+        {
+            action(ctx.clone()).await;
+        }
+
+        if self.log_capacity > 0 {
+            if self.log.len() == self.log_capacity {
+                self.log.pop_front();
+            }
+            self.log.push_back(record.clone());
+        }
+
+        Some(record)
+    }
+
+    /// The machine as a Graphviz digraph. Every transition is an edge labelled
+    /// with its message type and the number of times it was taken. Guarded
+    /// transitions are dashed, and the current state is drawn doubled.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n");
+
+        let _ = writeln!(
+            dot,
+            "    {} [shape=doublecircle];",
+            quote(&format!("{:?}", self.state))
+        );
+
+        for transition in &self.transitions {
+            let taken = transition.taken;
+            let style = if transition.guard.is_some() {
+                ", style=dashed"
+            } else {
+                ""
+            };
+
+            let _ = writeln!(
+                dot,
+                "    {} -> {} [label={}{style}];",
+                quote(&format!("{:?}", transition.from)),
+                quote(&format!("{:?}", transition.to)),
+                quote(&format!("{} ({taken})", transition.message_name)),
+            );
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn action_fn<F: Future<Output = ()> + 'static>(
+    mut action: impl FnMut(Context) -> F + 'static,
+) -> Action {
+    Box::new(move |ctx| action(ctx).boxed_local())
+}
+
+/// A DOT string literal.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl<S: State> fmt::Debug for StateMachine<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StateMachine")
+            .field("state", &self.state)
+            .field("transitions", &self.transitions.len())
+            .field("log", &self.log)
+            .finish()
+    }
+}
@@ -0,0 +1,116 @@
+//! A handle for controlling a [`System`](crate::system::System) from outside
+//! of it, e.g. from another thread, a GUI, or a test harness.
+
+use std::sync::Arc;
+
+use mekena_messaging::prelude::Message;
+use mekena_util::shutdown::{ShutdownManager, ShutdownReason};
+
+use crate::{
+    context::{ContextError, Shared},
+    event::EventStream,
+    mode::Mode,
+    node::{NodeId, NodeInfo},
+    system::SystemState,
+};
+
+/// A cloneable, [`Send`] handle to a system. Obtained through
+/// [`System::handle`](crate::system::System::handle), before starting it.
+#[derive(Clone, Debug)]
+pub struct SystemHandle {
+    shared: Arc<Shared>,
+    shutdown: ShutdownManager,
+}
+
+impl SystemHandle {
+    pub(crate) fn new(shared: Arc<Shared>, shutdown: ShutdownManager) -> Self {
+        Self { shared, shutdown }
+    }
+
+    /// The phase the system is currently in.
+    pub fn state(&self) -> SystemState {
+        self.shared.registry.phase()
+    }
+
+    /// The mode the system is in. See [`crate::mode`].
+    pub fn mode(&self) -> Mode {
+        self.shared.registry.mode()
+    }
+
+    /// Ask the system to switch to another mode, e.g. from a driver station.
+    /// See [`crate::mode`].
+    pub fn request_mode(&self, mode: Mode) {
+        self.shared.registry.set_mode(mode)
+    }
+
+    /// Subscribe to the system's [`LifecycleEvent`](crate::event::LifecycleEvent)s.
+    pub fn events(&self) -> EventStream {
+        self.shared.registry.subscribe()
+    }
+
+    /// The system's current run cycle, counting from 1, or 0 if it hasn't
+    /// started yet.
+    pub fn cycle(&self) -> u64 {
+        self.shared.registry.cycle_number()
+    }
+
+    /// End the current run cycle. See
+    /// [`Context::end_cycle`](crate::context::Context::end_cycle).
+    pub fn end_cycle(&self) {
+        self.shared.registry.end_cycle(ShutdownReason::Requested)
+    }
+
+    /// Signal the system to shut down with [`ShutdownReason::Requested`].
+    pub fn shutdown(&self) {
+        self.shutdown.shutdown()
+    }
+
+    /// Signal the system to shut down for the given reason. Only the first
+    /// reason given is kept.
+    pub fn shutdown_with(&self, reason: ShutdownReason) {
+        self.shutdown.shutdown_with(reason)
+    }
+
+    /// Send any message: [`Message`] to the system's mailbox, as if a node had
+    /// sent it.
+    pub async fn send<M: Message + 'static>(&self, message: M) -> Result<(), ContextError> {
+        self.shared
+            .mailbox
+            .send(message)
+            .await
+            .map_err(ContextError::from)
+    }
+
+    /// A snapshot of every node in the system, including those spawned at
+    /// runtime.
+    pub fn nodes(&self) -> Vec<NodeInfo> {
+        self.shared.registry.infos()
+    }
+
+    /// A snapshot of the node with the given ID.
+    pub fn node(&self, id: NodeId) -> Option<NodeInfo> {
+        self.shared.registry.info(id)
+    }
+
+    /// A snapshot of the first node with the given name.
+    pub fn node_named(&self, name: &str) -> Option<NodeInfo> {
+        self.nodes().into_iter().find(|node| node.name() == name)
+    }
+
+    /// Ask a node to pause. See
+    /// [`Context::pause_node`](crate::context::Context::pause_node).
+    pub fn pause(&self, id: NodeId) -> bool {
+        self.shared.registry.request_pause(id, true)
+    }
+
+    /// Ask a paused node to resume. See
+    /// [`Context::resume_node`](crate::context::Context::resume_node).
+    pub fn resume(&self, id: NodeId) -> bool {
+        self.shared.registry.request_pause(id, false)
+    }
+
+    /// Wait until the system has finished stopping, and return why it stopped.
+    pub async fn join(&self) -> ShutdownReason {
+        self.shared.registry.wait_finished().await
+    }
+}
@@ -0,0 +1,381 @@
+//! Wall-clock jobs, for work that runs at times of day rather than at a rate.
+//!
+//! A [`Job`] runs on a [`Cron`] expression, evaluated in UTC, or at a fixed
+//! interval. When it is due it either sends a message to the mailbox, or calls
+//! a function. Nodes add jobs with [`Context::add_job`], list them with
+//! [`Context::jobs`], and they are run by a [`JobScheduler`] node, which must
+//! be added to the system.
+//!
+//! Runs that were due while the system wasn't running, or while the scheduler
+//! couldn't keep up, are missed, and handled according to the job's
+//! [`MissedRuns`]. Runs missed before the system started are only known of if
+//! the job is told when it last ran, with [`Job::last_run`].
+//!
+//! For real-time periodic work, see [`crate::periodic`] instead.
+//!
+//! [`Context::add_job`]: crate::context::Context::add_job
+//! [`Context::jobs`]: crate::context::Context::jobs
+
+use std::{
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use futures::{future::LocalBoxFuture, FutureExt};
+use mekena_messaging::prelude::Message;
+use tokio::time::MissedTickBehavior;
+
+use crate::{context::Context, node::Node};
+
+pub mod cron;
+
+pub use cron::Cron;
+
+/// The most runs caught up on at once, when missed runs are all run.
+const MAX_CATCH_UP: usize = 1024;
+
+type Run = Box<dyn FnMut(&Context) -> LocalBoxFuture<'static, ()>>;
+
+/// When a job runs.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Every {
+    Cron(Cron),
+    Interval(Duration),
+}
+
+impl Every {
+    /// The first time the job runs after `time`, if it ever does.
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        match self {
+            Self::Cron(cron) => cron.next_after(time),
+            Self::Interval(interval) => time.checked_add(*interval),
+        }
+    }
+}
+
+impl fmt::Display for Every {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cron(cron) => write!(f, "{cron}"),
+            Self::Interval(interval) => write!(f, "every {interval:?}"),
+        }
+    }
+}
+
+/// What to do about runs that were due while the job couldn't run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MissedRuns {
+    /// Drop them, and wait for the next run.
+    #[default]
+    Skip,
+    /// Run once to make up for them.
+    RunOnce,
+    /// Run once for each of them.
+    RunAll,
+}
+
+/// A job, which does nothing until it is given something to do with
+/// [`send`](Job::send) or [`call`](Job::call).
+pub struct Job {
+    name: String,
+    every: Every,
+    missed: MissedRuns,
+    last_run: Option<SystemTime>,
+    run: Run,
+}
+
+impl Job {
+    /// A job on a cron expression. See [`Cron`].
+    pub fn cron(name: impl Into<String>, expression: &str) -> Result<Self, JobError> {
+        Ok(Self::new(name, Every::Cron(Cron::parse(expression)?)))
+    }
+
+    /// A job that runs every `interval`, which can't be zero.
+    pub fn interval(name: impl Into<String>, interval: Duration) -> Result<Self, JobError> {
+        let job = Self::new(name, Every::Interval(interval));
+        job.check()?;
+        Ok(job)
+    }
+
+    pub fn new(name: impl Into<String>, every: Every) -> Self {
+        Self {
+            name: name.into(),
+            every,
+            missed: MissedRuns::default(),
+            last_run: None,
+            run: Box::new(|_| async {}.boxed_local()),
+        }
+    }
+
+    /// Send a message made by `message` each time the job runs.
+    pub fn send<M: Message + 'static>(mut self, mut message: impl FnMut() -> M + 'static) -> Self {
+        self.run = Box::new(move |ctx| {
+            let message = message();
+            let mailbox = ctx.mailbox().clone();
+            async move {
+                let _ = mailbox.send(message).await;
+            }
+            .boxed_local()
+        });
+        self
+    }
+
+    /// Call `f` each time the job runs.
+    pub fn call(mut self, mut f: impl FnMut(&Context) + 'static) -> Self {
+        self.run = Box::new(move |ctx| {
+            f(ctx);
+            async {}.boxed_local()
+        });
+        self
+    }
+
+    pub fn missed_runs(mut self, missed: MissedRuns) -> Self {
+        self.missed = missed;
+        self
+    }
+
+    /// When the job last ran, e.g. before the program restarted, so runs
+    /// missed since can be caught up on.
+    pub fn last_run(mut self, time: SystemTime) -> Self {
+        self.last_run = Some(time);
+        self
+    }
+
+    /// Check that the job doesn't run every instant.
+    fn check(&self) -> Result<(), JobError> {
+        match self.every {
+            Every::Interval(interval) if interval.is_zero() => Err(JobError::ZeroInterval {
+                name: self.name.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Job")
+            .field("name", &self.name)
+            .field("every", &self.every)
+            .field("missed", &self.missed)
+            .field("last_run", &self.last_run)
+            .finish()
+    }
+}
+
+/// A snapshot of a job, from [`Context::jobs`](crate::context::Context::jobs).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JobInfo {
+    pub name: String,
+    pub every: Every,
+    pub missed: MissedRuns,
+    /// `None` if it will never run again.
+    pub next_run: Option<SystemTime>,
+    pub last_run: Option<SystemTime>,
+    /// How many times it has run since it was added.
+    pub runs: u64,
+}
+
+/// Runs jobs. Add exactly one to a system that uses jobs: without it, jobs
+/// never run.
+#[derive(Debug)]
+pub struct JobScheduler {
+    resolution: Duration,
+}
+
+impl JobScheduler {
+    /// Construct a scheduler that checks for due jobs every second.
+    pub fn new() -> Self {
+        Self {
+            resolution: Duration::from_secs(1),
+        }
+    }
+
+    /// How often to check for due jobs. Runs are late by up to this much, and
+    /// runs later than this count as missed. Fails if `resolution` is zero.
+    pub fn resolution(self, resolution: Duration) -> Result<Self, JobError> {
+        if resolution.is_zero() {
+            return Err(JobError::ZeroResolution);
+        }
+        Ok(Self { resolution })
+    }
+}
+
+impl Default for JobScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Node for JobScheduler {
+    async fn running(&mut self, ctx: &Context) {
+        let mut interval = tokio::time::interval(self.resolution);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+
+            let due = ctx
+                .local()
+                .jobs()
+                .take_due(SystemTime::now(), self.resolution);
+
+            // Jobs' functions may add or remove jobs, so the table can't stay
+            // borrowed while they're called.
+            let mut runs = Vec::new();
+            for (name, count) in due {
+                let mut run = match ctx.local().jobs().take_run(&name) {
+                    Some(run) => run,
+                    None => continue,
+                };
+                runs.extend((0..count).map(|_| run(ctx)));
+                ctx.local().jobs().restore_run(&name, run);
+            }
+
+            for run in runs {
+                run.await;
+            }
+        }
+    }
+}
+
+/// The jobs of a system, shared between the nodes adding them and the
+/// [`JobScheduler`] running them.
+#[derive(Debug, Default)]
+pub(crate) struct Table {
+    jobs: Vec<Entry>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    job: Job,
+    next_run: Option<SystemTime>,
+    runs: u64,
+}
+
+impl Table {
+    pub fn add(&mut self, job: Job) -> Result<(), JobError> {
+        if self.jobs.iter().any(|entry| entry.job.name == job.name) {
+            return Err(JobError::Duplicate { name: job.name });
+        }
+        job.check()?;
+
+        let next_run = job
+            .every
+            .next_after(job.last_run.unwrap_or_else(SystemTime::now));
+        self.jobs.push(Entry {
+            job,
+            next_run,
+            runs: 0,
+        });
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.jobs.len();
+        self.jobs.retain(|entry| entry.job.name != name);
+        self.jobs.len() != before
+    }
+
+    /// Take a job's function out while it is called. See [`Self::restore_run`].
+    fn take_run(&mut self, name: &str) -> Option<Run> {
+        let entry = self.jobs.iter_mut().find(|entry| entry.job.name == name)?;
+        Some(std::mem::replace(
+            &mut entry.job.run,
+            Box::new(|_| async {}.boxed_local()),
+        ))
+    }
+
+    /// Put a job's function back, if the job wasn't removed meanwhile.
+    fn restore_run(&mut self, name: &str, run: Run) {
+        if let Some(entry) = self.jobs.iter_mut().find(|entry| entry.job.name == name) {
+            entry.job.run = run;
+        }
+    }
+
+    pub fn infos(&self) -> Vec<JobInfo> {
+        self.jobs
+            .iter()
+            .map(|entry| JobInfo {
+                name: entry.job.name.clone(),
+                every: entry.job.every.clone(),
+                missed: entry.job.missed,
+                next_run: entry.next_run,
+                last_run: entry.job.last_run,
+                runs: entry.runs,
+            })
+            .collect()
+    }
+
+    /// How many times each job should run by `now`, moving every job on to its
+    /// next run. Runs due more than `grace` ago were missed.
+    fn take_due(&mut self, now: SystemTime, grace: Duration) -> Vec<(String, usize)> {
+        let mut due = Vec::new();
+
+        for entry in &mut self.jobs {
+            let mut on_time = 0;
+            let mut missed = 0;
+            while let Some(next) = entry.next_run.filter(|&next| next <= now) {
+                match now.duration_since(next) {
+                    Ok(late) if late > grace => missed += 1,
+                    _ => on_time += 1,
+                }
+                entry.next_run = entry.job.every.next_after(next);
+
+                if on_time + missed == MAX_CATCH_UP {
+                    // Don't fall further behind than this.
+                    entry.next_run = entry.job.every.next_after(now);
+                    break;
+                }
+            }
+
+            let runs = on_time
+                + match entry.job.missed {
+                    MissedRuns::Skip => 0,
+                    MissedRuns::RunOnce if on_time == 0 => missed.min(1),
+                    MissedRuns::RunOnce => 0,
+                    MissedRuns::RunAll => missed,
+                };
+
+            if runs > 0 {
+                due.push((entry.job.name.clone(), runs));
+                entry.job.last_run = Some(now);
+                entry.runs += runs as u64;
+            }
+        }
+
+        due
+    }
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug, Clone, PartialEq, Eq)]
+pub enum JobError {
+    #[error("{expression:?} isn't a valid cron expression: {reason}.")]
+    #[diagnostic(
+        code(mekena::job::invalid_cron),
+        help("Cron expressions have 5 fields: minute, hour, day of month, month and day of week.")
+    )]
+    InvalidCron { expression: String, reason: String },
+
+    #[error("Job {name:?} has a zero interval.")]
+    #[diagnostic(
+        code(mekena::job::zero_interval),
+        help("A job can't run continuously. Give it an interval of at least the scheduler's resolution.")
+    )]
+    ZeroInterval { name: String },
+
+    #[error("The job scheduler has a resolution of zero.")]
+    #[diagnostic(
+        code(mekena::job::zero_resolution),
+        help("The scheduler can't check for due jobs continuously. Give it a resolution of at least a millisecond.")
+    )]
+    ZeroResolution,
+
+    #[error("There is already a job named {name:?}.")]
+    #[diagnostic(
+        code(mekena::job::duplicate),
+        help("Remove the existing job first, or give this one another name.")
+    )]
+    Duplicate { name: String },
+}
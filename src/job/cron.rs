@@ -0,0 +1,304 @@
+//! Cron expressions, evaluated in UTC.
+
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::JobError;
+
+/// How far ahead to look for a matching minute, in days, before concluding
+/// that an expression never matches, e.g. `0 0 30 2 *`.
+const HORIZON: u64 = 5 * 366;
+
+/// A cron expression: minute, hour, day of month, month and day of week.
+///
+/// Each field is `*`, a number, a range `a-b`, any of those with a step `/n`,
+/// or a comma-separated list of them. Days of the week count from Sunday, as
+/// 0 or 7. As in most crons, when both the day of month and day of week are
+/// restricted, a day matching either matches. `@yearly`, `@monthly`,
+/// `@weekly`, `@daily` and `@hourly` are accepted too.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Cron {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day of month and day of week fields were `*`-based.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub fn parse(expression: &str) -> Result<Self, JobError> {
+        let invalid = |reason: String| JobError::InvalidCron {
+            expression: expression.to_string(),
+            reason,
+        };
+
+        let expanded = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let (minutes, hours, days, months, weekdays) = match fields[..] {
+            [minutes, hours, days, months, weekdays] => (minutes, hours, days, months, weekdays),
+            _ => {
+                return Err(invalid(format!(
+                    "expected 5 fields, found {}",
+                    fields.len()
+                )))
+            }
+        };
+
+        let mut weekday_bits = field(weekdays, 0, 7).map_err(invalid)?;
+        // Sunday is both 0 and 7.
+        if weekday_bits & 1 << 7 != 0 {
+            weekday_bits = (weekday_bits & !(1 << 7)) | 1;
+        }
+
+        Ok(Self {
+            expression: expression.trim().to_string(),
+            minutes: field(minutes, 0, 59).map_err(invalid)?,
+            hours: field(hours, 0, 23).map_err(invalid)?,
+            days: field(days, 1, 31).map_err(invalid)?,
+            months: field(months, 1, 12).map_err(invalid)?,
+            weekdays: weekday_bits,
+            any_day: days.starts_with('*'),
+            any_weekday: weekdays.starts_with('*'),
+        })
+    }
+
+    /// The first whole minute after `time` that matches, if any does.
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        let first = secs / 60 + 1;
+        let first_day = first / 1440;
+
+        for day in first_day..first_day + HORIZON {
+            if !self.matches_day(day) {
+                continue;
+            }
+
+            let start = if day == first_day { first % 1440 } else { 0 };
+            for minute_of_day in start..1440 {
+                if bit(self.hours, minute_of_day / 60) && bit(self.minutes, minute_of_day % 60) {
+                    let minute = day * 1440 + minute_of_day;
+                    return Some(UNIX_EPOCH + Duration::from_secs(minute * 60));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Whether the day, counted from the Unix epoch, matches.
+    fn matches_day(&self, day: u64) -> bool {
+        let (_, month, day_of_month) = civil_from_days(day);
+        if !bit(self.months, month) {
+            return false;
+        }
+
+        // The epoch was a Thursday.
+        let weekday = (day + 4) % 7;
+        let day_matches = bit(self.days, day_of_month);
+        let weekday_matches = bit(self.weekdays, weekday);
+
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday_matches,
+            (false, true) => day_matches,
+            (false, false) => day_matches || weekday_matches,
+        }
+    }
+}
+
+impl FromStr for Cron {
+    type Err = JobError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl fmt::Debug for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Cron").field(&self.expression).finish()
+    }
+}
+
+fn bit(bits: u64, n: u64) -> bool {
+    bits & 1 << n != 0
+}
+
+/// The values a field matches, as bits.
+fn field(text: &str, min: u64, max: u64) -> Result<u64, String> {
+    let number = |text: &str| {
+        text.parse::<u64>()
+            .ok()
+            .filter(|n| (min..=max).contains(n))
+            .ok_or_else(|| format!("{text:?} isn't a number from {min} to {max}"))
+    };
+
+    let mut bits = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|&step| step > 0)
+                    .ok_or_else(|| format!("{step:?} isn't a valid step"))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (number(start)?, number(end)?),
+                // `a/n` runs from `a` to the end.
+                None if step > 1 => (number(range)?, max),
+                None => {
+                    let n = number(range)?;
+                    (n, n)
+                }
+            },
+        };
+
+        if start > end {
+            return Err(format!("{part:?} is an empty range"));
+        }
+        for n in (start..=end).step_by(step as usize) {
+            bits |= 1 << n;
+        }
+    }
+
+    Ok(bits)
+}
+
+/// The year, month and day of a day counted from the Unix epoch. See
+/// <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Days from the epoch to 2024-01-01, a Monday.
+    const JAN_1: u64 = 19_723;
+
+    fn time(day: u64, minute: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs((day * 1440 + minute) * 60)
+    }
+
+    fn bits(ns: &[u64]) -> u64 {
+        ns.iter().fold(0, |bits, n| bits | 1 << n)
+    }
+
+    #[test]
+    fn parses_fields() {
+        assert_eq!(field("*", 0, 6), Ok(bits(&[0, 1, 2, 3, 4, 5, 6])));
+        assert_eq!(field("5", 0, 59), Ok(bits(&[5])));
+        assert_eq!(field("1-3,7", 0, 59), Ok(bits(&[1, 2, 3, 7])));
+        assert_eq!(field("*/15", 0, 59), Ok(bits(&[0, 15, 30, 45])));
+        assert_eq!(field("10/20", 0, 59), Ok(bits(&[10, 30, 50])));
+        assert_eq!(field("1-10/4", 1, 31), Ok(bits(&[1, 5, 9])));
+
+        assert!(field("60", 0, 59).is_err());
+        assert!(field("0", 1, 31).is_err());
+        assert!(field("5-1", 0, 59).is_err());
+        assert!(field("*/0", 0, 59).is_err());
+        assert!(field("a", 0, 59).is_err());
+        assert!(Cron::parse("0 0 * *").is_err());
+        assert!(Cron::parse("0 0 * * * *").is_err());
+    }
+
+    #[test]
+    fn sunday_is_0_and_7() {
+        let zero = Cron::parse("0 0 * * 0").unwrap();
+        let seven = Cron::parse("0 0 * * 7").unwrap();
+        assert_eq!(zero.weekdays, seven.weekdays);
+
+        let sunday = Some(time(JAN_1 + 6, 0));
+        assert_eq!(zero.next_after(time(JAN_1, 0)), sunday);
+        assert_eq!(seven.next_after(time(JAN_1, 0)), sunday);
+        assert_eq!(
+            Cron::parse("@weekly").unwrap().next_after(time(JAN_1, 0)),
+            sunday
+        );
+    }
+
+    #[test]
+    fn day_of_month_or_day_of_week() {
+        // The 13th, or any Friday. 2024-01-05 is a Friday, and 2024-01-13 a
+        // Saturday.
+        let cron = Cron::parse("0 0 13 * 5").unwrap();
+        assert_eq!(cron.next_after(time(JAN_1, 0)), Some(time(JAN_1 + 4, 0)));
+        assert_eq!(
+            cron.next_after(time(JAN_1 + 4, 0)),
+            Some(time(JAN_1 + 11, 0))
+        );
+        assert_eq!(
+            cron.next_after(time(JAN_1 + 11, 0)),
+            Some(time(JAN_1 + 12, 0))
+        );
+
+        // With either field `*`, only the other one counts.
+        let cron = Cron::parse("0 0 13 * *").unwrap();
+        assert_eq!(cron.next_after(time(JAN_1, 0)), Some(time(JAN_1 + 12, 0)));
+        let cron = Cron::parse("0 0 * * 5").unwrap();
+        assert_eq!(
+            cron.next_after(time(JAN_1 + 4, 0)),
+            Some(time(JAN_1 + 11, 0))
+        );
+    }
+
+    #[test]
+    fn next_after_is_strictly_later() {
+        let cron = Cron::parse("*/30 9 * * *").unwrap();
+        assert_eq!(cron.next_after(time(JAN_1, 0)), Some(time(JAN_1, 9 * 60)));
+        assert_eq!(
+            cron.next_after(time(JAN_1, 9 * 60)),
+            Some(time(JAN_1, 9 * 60 + 30))
+        );
+        assert_eq!(
+            cron.next_after(time(JAN_1, 9 * 60 + 30)),
+            Some(time(JAN_1 + 1, 9 * 60))
+        );
+    }
+
+    #[test]
+    fn never_matches() {
+        let cron = Cron::parse("0 0 30 2 *").unwrap();
+        assert_eq!(cron.next_after(time(JAN_1, 0)), None);
+    }
+}
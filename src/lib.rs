@@ -1,17 +1,48 @@
+pub mod behavior;
+pub mod command;
 pub mod context;
+mod dependency;
+pub mod event;
+pub mod fsm;
+pub mod handle;
+pub mod job;
+pub mod mode;
 pub mod node;
+pub mod periodic;
+mod registry;
+pub mod signal;
+pub mod subsystem;
 pub mod system;
+pub mod watchdog;
+pub mod wiring;
 
 pub mod prelude {
     pub use mekena_messaging::prelude::*;
+    pub use mekena_state::{state_key, Key, StateError, StateKey, StateRef, StateRefMut};
+    pub use mekena_util::shutdown::ShutdownReason;
 
+    pub use crate::behavior::{Behavior, BehaviorTree, Leaves, Status};
+    pub use crate::command::{
+        Command, CommandExt, CommandId, CommandScheduler, InterruptionBehavior, Resource,
+        ScheduleError, Trigger,
+    };
     pub use crate::context::{Context, ContextError};
-    pub use crate::node::Node;
+    pub use crate::event::{EventStream, LifecycleEvent, NodeEvent};
+    pub use crate::fsm::{StateMachine, TransitionRecord};
+    pub use crate::handle::SystemHandle;
+    pub use crate::job::{Job, JobScheduler, MissedRuns};
+    pub use crate::mode::{Mode, ModeConfig};
+    pub use crate::node::{Node, NodeConfig, NodeHandle, NodeId, NodeInfo, NodeState};
+    pub use crate::periodic::{MissedTicks, TickStats};
+    pub use crate::signal::SignalConfig;
+    pub use crate::subsystem::Subsystem;
     pub use crate::system::{System, SystemError};
-    pub use crate::{main, node};
+    pub use crate::watchdog::{Watchdog, WatchdogAction};
+    pub use crate::wiring::{Group, Messages};
+    pub use crate::{command, main, node, system};
 }
 
-pub use mekena_macros::{main, node};
+pub use mekena_macros::{command, main, node, system};
 
 pub mod re {
     pub use async_trait;
@@ -0,0 +1,94 @@
+//! Robot-style operating modes. See [`System::modes`].
+//!
+//! While modes are enabled, every node's mode hooks are driven once its
+//! `running` hook returns: [`Node::mode_enter`] when a mode is entered,
+//! [`Node::mode_periodic`] once every period while it lasts, and
+//! [`Node::mode_exit`] when it is left. Any node can switch the whole system to
+//! another mode with [`Context::request_mode`], and so can the outside world
+//! with [`SystemHandle::request_mode`].
+//!
+//! Every `mode_enter` is matched by a `mode_exit`, including when the node
+//! stops, because the system shuts down or its run cycle ends: then
+//! `mode_exit` is called for the current mode first, and `stopping` after it.
+//! The same goes for a node that is paused or restarted, whose `mode_exit` is
+//! called before `pause` or `stopping`, and whose `mode_enter` is called again
+//! once it runs again.
+//!
+//! [`System::modes`]: crate::system::System::modes
+//! [`Node::mode_enter`]: crate::node::Node::mode_enter
+//! [`Node::mode_periodic`]: crate::node::Node::mode_periodic
+//! [`Node::mode_exit`]: crate::node::Node::mode_exit
+//! [`Context::request_mode`]: crate::context::Context::request_mode
+//! [`SystemHandle::request_mode`]: crate::handle::SystemHandle::request_mode
+
+use std::{fmt, time::Duration};
+
+/// An operating mode, identified by its name. A few common ones are provided,
+/// and you can define your own as constants with [`Mode::new`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Mode(&'static str);
+
+impl Mode {
+    /// The mode a system starts in, unless configured otherwise.
+    pub const DISABLED: Mode = Mode::new("disabled");
+    pub const AUTONOMOUS: Mode = Mode::new("autonomous");
+    pub const TELEOP: Mode = Mode::new("teleop");
+    pub const TEST: Mode = Mode::new("test");
+
+    pub const fn new(name: &'static str) -> Self {
+        Self(name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0
+    }
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Self::DISABLED
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// Which mode the system starts in, and how often the mode hooks run.
+#[derive(Clone, Copy, Debug)]
+pub struct ModeConfig {
+    pub(crate) initial: Mode,
+    pub(crate) period: Duration,
+}
+
+impl ModeConfig {
+    /// Construct the default config: start [`Mode::DISABLED`], and call
+    /// `mode_periodic` every 20ms.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The mode the system starts in.
+    pub fn initial(mut self, mode: Mode) -> Self {
+        self.initial = mode;
+        self
+    }
+
+    /// How often `mode_periodic` is called. If a call runs over, the ticks it
+    /// missed are skipped. The system won't start if `period` is zero.
+    pub fn period(mut self, period: Duration) -> Self {
+        self.period = period;
+        self
+    }
+}
+
+impl Default for ModeConfig {
+    fn default() -> Self {
+        Self {
+            initial: Mode::DISABLED,
+            period: Duration::from_millis(20),
+        }
+    }
+}
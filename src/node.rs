@@ -1,11 +1,320 @@
-//! A node is an element of a [`System`]. It can be composed with other nodes.
-//! It can send and recieve messages.
+//! A node is an element of a [`System`]. It can be composed with other nodes
+//! into a [`Subsystem`]. It can send and recieve messages.
+//!
+//! [`System`]: crate::system::System
+//! [`Subsystem`]: crate::subsystem::Subsystem
 
-use crate::context::Context;
+use std::{any::TypeId, fmt, sync::Arc, time::Duration};
+
+use mekena_util::shutdown::ShutdownManager;
+use tokio::sync::watch;
+
+use crate::{
+    context::{Context, Shared},
+    mode::Mode,
+    periodic::{MissedTicks, Schedule, TickStats},
+    watchdog::Watchdog,
+};
 
 #[async_trait::async_trait(?Send)]
 pub trait Node {
     async fn starting(&mut self, _ctx: &Context) {}
     async fn running(&mut self, _ctx: &Context) {}
     async fn stopping(&mut self, _ctx: &Context) {}
+
+    /// Called when the node is paused. The `running` hook is cancelled at its
+    /// next `.await` before this is called.
+    async fn pause(&mut self, _ctx: &Context) {}
+
+    /// Called when the node is resumed after a pause. The `running` hook is
+    /// called again from the start after this returns.
+    async fn resume(&mut self, _ctx: &Context) {}
+
+    /// Called when the system enters `mode`, or when this node starts running
+    /// while the system is in it. Only called once `running` returns, and only
+    /// if modes are enabled with [`System::modes`].
+    ///
+    /// [`System::modes`]: crate::system::System::modes
+    async fn mode_enter(&mut self, _ctx: &Context, _mode: Mode) {}
+
+    /// Called periodically while the system is in `mode`.
+    async fn mode_periodic(&mut self, _ctx: &Context, _mode: Mode) {}
+
+    /// Called when the system leaves `mode` for another, or before `stopping`
+    /// or `pause` while the node is in `mode`.
+    async fn mode_exit(&mut self, _ctx: &Context, _mode: Mode) {}
+
+    /// Called once every period, for nodes configured with
+    /// [`NodeConfig::period`]. See [`crate::periodic`].
+    async fn tick(&mut self, _ctx: &Context) {}
+}
+
+/// The phase a single node is in. See also
+/// [`SystemState`](crate::system::SystemState) for the whole system.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NodeState {
+    /// The node is registered, but not ready yet. It may be waiting on its
+    /// dependencies, or in its `starting` hook.
+    Starting,
+    /// The node is ready, and waiting for the rest of the system to be.
+    Ready,
+    /// The node's `running` hook has begun.
+    Running,
+    /// The node was paused, and its `running` hook cancelled.
+    Paused,
+    /// The node's `stopping` hook has begun.
+    Stopping,
+    /// The node's `stopping` hook has returned.
+    Stopped,
+    /// One of the node's hooks panicked, or, for a composite node such as a
+    /// [`Subsystem`](crate::subsystem::Subsystem), one of its children failed.
+    Failed,
+}
+
+/// Uniquely identifies a node within a [`System`](crate::system::System).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(pub(crate) u64);
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// A snapshot of a node's status. See
+/// [`SystemHandle::nodes`](crate::handle::SystemHandle::nodes).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeInfo {
+    pub(crate) id: NodeId,
+    pub(crate) name: String,
+    pub(crate) ready: bool,
+    pub(crate) state: NodeState,
+    pub(crate) ticks: Option<TickStats>,
+}
+
+impl NodeInfo {
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns `true` once the node has become ready. This stays `true`
+    /// afterwards, even when the node is paused or stopped.
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.state == NodeState::Failed
+    }
+
+    pub fn state(&self) -> NodeState {
+        self.state
+    }
+
+    /// How closely the node has kept to its schedule, if it is periodic.
+    pub fn tick_stats(&self) -> Option<TickStats> {
+        self.ticks
+    }
+}
+
+/// How a node is registered with a [`System`](crate::system::System). See
+/// [`System::add_node_with`](crate::system::System::add_node_with).
+#[derive(Clone, Debug, Default)]
+pub struct NodeConfig {
+    pub(crate) name: Option<String>,
+    pub(crate) dependencies: Vec<Dependency>,
+    pub(crate) manual_ready: bool,
+    pub(crate) startup_timeout: Option<Duration>,
+    pub(crate) period: Option<Duration>,
+    pub(crate) missed_ticks: MissedTicks,
+    pub(crate) watchdog: Option<Watchdog>,
+}
+
+/// The parts of a [`NodeConfig`] the system keeps track of while the node
+/// runs.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct NodeOptions {
+    pub schedule: Option<Schedule>,
+    pub watchdog: Option<Watchdog>,
+}
+
+impl NodeConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name the node. Defaults to the node's type name.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Only start this node once every node of type `N` is ready, and stop it
+    /// before they stop.
+    pub fn depends_on<N: Node + 'static>(mut self) -> Self {
+        self.dependencies.push(Dependency::Type(
+            TypeId::of::<N>(),
+            std::any::type_name::<N>(),
+        ));
+        self
+    }
+
+    /// Only start this node once the node with the given name is ready, and
+    /// stop it before that node stops.
+    pub fn depends_on_named(mut self, name: impl Into<String>) -> Self {
+        self.dependencies.push(Dependency::Name(name.into()));
+        self
+    }
+
+    /// Don't consider the node ready when its `starting` hook returns. It must
+    /// call [`Context::ready`] instead, either from `starting` or from
+    /// `running`, which begins as soon as `starting` returns so the node can
+    /// finish getting ready (e.g. waiting for a sensor to calibrate).
+    pub fn manual_ready(mut self) -> Self {
+        self.manual_ready = true;
+        self
+    }
+
+    /// Fail the system's startup if the node isn't ready this long after its
+    /// `starting` hook begins.
+    pub fn startup_timeout(mut self, timeout: Duration) -> Self {
+        self.startup_timeout = Some(timeout);
+        self
+    }
+
+    /// Call the node's [`Node::tick`] hook once every `period`, once its
+    /// `running` hook returns. Periodic nodes don't have their mode hooks
+    /// called. The system won't start if `period` is zero. See
+    /// [`crate::periodic`].
+    pub fn period(mut self, period: Duration) -> Self {
+        self.period = Some(period);
+        self
+    }
+
+    /// What to do with ticks missed because a tick ran over. Defaults to
+    /// [`MissedTicks::Skip`].
+    pub fn missed_ticks(mut self, missed: MissedTicks) -> Self {
+        self.missed_ticks = missed;
+        self
+    }
+
+    /// Watch the node for missed deadlines. See [`crate::watchdog`].
+    pub fn watchdog(mut self, watchdog: Watchdog) -> Self {
+        self.watchdog = Some(watchdog);
+        self
+    }
+
+    pub(crate) fn options(&self) -> NodeOptions {
+        NodeOptions {
+            schedule: self.period.map(|period| Schedule {
+                period,
+                missed: self.missed_ticks,
+            }),
+            watchdog: self.watchdog,
+        }
+    }
+}
+
+/// Something a node depends on, either every node of a type, or a node by name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Dependency {
+    Type(TypeId, &'static str),
+    Name(String),
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Type(_, name) => write!(f, "{name}"),
+            Self::Name(name) => write!(f, "{name:?}"),
+        }
+    }
+}
+
+/// A handle to a node spawned with
+/// [`Context::spawn_node`](crate::context::Context::spawn_node).
+#[derive(Clone, Debug)]
+pub struct NodeHandle {
+    id: NodeId,
+    name: &'static str,
+    shared: Arc<Shared>,
+    scope: ShutdownManager,
+    finished: watch::Receiver<bool>,
+}
+
+impl NodeHandle {
+    pub(crate) fn new(
+        id: NodeId,
+        name: &'static str,
+        shared: Arc<Shared>,
+        scope: ShutdownManager,
+        finished: watch::Receiver<bool>,
+    ) -> Self {
+        Self {
+            id,
+            name,
+            shared,
+            scope,
+            finished,
+        }
+    }
+
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    /// The type name of the node.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Ask the node to stop. Its `running` hook is cut short, and its
+    /// `stopping` hook is run. Any nodes it spawned are stopped too.
+    pub fn stop(&self) {
+        self.scope.shutdown()
+    }
+
+    /// Ask the node to pause. See [`Context::pause_node`].
+    ///
+    /// [`Context::pause_node`]: crate::context::Context::pause_node
+    pub fn pause(&self) {
+        self.shared.registry.request_pause(self.id, true);
+    }
+
+    /// Ask the node to resume after a pause.
+    pub fn resume(&self) {
+        self.shared.registry.request_pause(self.id, false);
+    }
+
+    /// The phase the node is currently in.
+    pub fn state(&self) -> NodeState {
+        // Spawned nodes are forgotten once the run cycle they were spawned in
+        // has ended, by which point they have stopped.
+        self.shared
+            .registry
+            .state(self.id)
+            .unwrap_or(NodeState::Stopped)
+    }
+
+    /// Returns `true` once the node's `stopping` hook has completed.
+    pub fn is_finished(&self) -> bool {
+        *self.finished.borrow()
+    }
+
+    /// Wait until the node's `stopping` hook has completed.
+    pub async fn join(&self) {
+        let mut finished = self.finished.clone();
+
+        while !*finished.borrow() {
+            if finished.changed().await.is_err() {
+                // The node was dropped without finishing, e.g. because the
+                // system itself was dropped. It won't finish now.
+                return;
+            }
+        }
+    }
 }
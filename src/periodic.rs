@@ -0,0 +1,109 @@
+//! Fixed-rate periodic execution. See [`NodeConfig::period`].
+//!
+//! A periodic node's [`Node::tick`] hook is called once every period, once its
+//! `running` hook returns. Ticks are scheduled from when the first one was
+//! due, not from when the last one finished, so they don't drift. When a tick
+//! runs over its period, [`MissedTicks`] decides what happens to the ticks
+//! that should have happened in the meantime.
+//!
+//! How closely a node keeps to its schedule is tracked in its [`TickStats`].
+//!
+//! [`NodeConfig::period`]: crate::node::NodeConfig::period
+//! [`Node::tick`]: crate::node::Node::tick
+
+use std::time::Duration;
+
+use tokio::time::MissedTickBehavior;
+
+/// What happens to ticks that were missed because a tick ran over.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MissedTicks {
+    /// Run every missed tick as soon as possible, one after another, to catch
+    /// up with the schedule.
+    Burst,
+    /// Drop the missed ticks, and carry on with the next one on the original
+    /// schedule.
+    #[default]
+    Skip,
+    /// Run one tick as soon as possible, then shift the schedule so the next
+    /// one is a whole period after it.
+    Delay,
+}
+
+impl From<MissedTicks> for MissedTickBehavior {
+    fn from(missed: MissedTicks) -> Self {
+        match missed {
+            MissedTicks::Burst => MissedTickBehavior::Burst,
+            MissedTicks::Skip => MissedTickBehavior::Skip,
+            MissedTicks::Delay => MissedTickBehavior::Delay,
+        }
+    }
+}
+
+/// When a periodic node ticks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Schedule {
+    pub period: Duration,
+    pub missed: MissedTicks,
+}
+
+/// How closely a periodic node has kept to its schedule in the current run
+/// cycle.
+///
+/// Jitter is how late a tick started compared to when it was due. A tick
+/// overruns when it takes longer than the period.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TickStats {
+    ticks: u64,
+    overruns: u64,
+    last_jitter: Duration,
+    max_jitter: Duration,
+    total_jitter: Duration,
+    max_duration: Duration,
+}
+
+impl TickStats {
+    pub(crate) fn record(&mut self, period: Duration, jitter: Duration, duration: Duration) {
+        self.ticks += 1;
+        if duration > period {
+            self.overruns += 1;
+        }
+
+        self.last_jitter = jitter;
+        self.max_jitter = self.max_jitter.max(jitter);
+        self.total_jitter += jitter;
+        self.max_duration = self.max_duration.max(duration);
+    }
+
+    /// How many ticks have completed.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// How many ticks took longer than the period.
+    pub fn overruns(&self) -> u64 {
+        self.overruns
+    }
+
+    /// The jitter of the most recent tick.
+    pub fn last_jitter(&self) -> Duration {
+        self.last_jitter
+    }
+
+    pub fn max_jitter(&self) -> Duration {
+        self.max_jitter
+    }
+
+    pub fn mean_jitter(&self) -> Duration {
+        match u32::try_from(self.ticks) {
+            Ok(0) => Duration::ZERO,
+            Ok(ticks) => self.total_jitter / ticks,
+            Err(_) => self.total_jitter.div_f64(self.ticks as f64),
+        }
+    }
+
+    /// How long the longest tick took.
+    pub fn max_duration(&self) -> Duration {
+        self.max_duration
+    }
+}
@@ -0,0 +1,433 @@
+//! The shared record of the system's phase, and of every node in it. Every
+//! change is also broadcast as a [`LifecycleEvent`].
+
+use std::{
+    any::TypeId,
+    collections::BTreeMap,
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+use mekena_util::shutdown::{ShutdownManager, ShutdownReason};
+use tokio::sync::{broadcast, watch};
+use tokio::time::Instant;
+
+use crate::{
+    event::{self, EventStream, LifecycleEvent, NodeEvent},
+    mode::{Mode, ModeConfig},
+    node::{NodeId, NodeInfo, NodeOptions, NodeState},
+    periodic::{Schedule, TickStats},
+    system::SystemState,
+    watchdog::Watchdog,
+};
+
+/// Every node known to a system, static or spawned at runtime. Shared between
+/// all of the system's contexts.
+#[derive(Debug)]
+pub(crate) struct Registry {
+    nodes: Mutex<BTreeMap<NodeId, NodeRecord>>,
+    /// Bumped whenever a record changes, so waiters can re-check.
+    changed: watch::Sender<()>,
+    phase: watch::Sender<SystemState>,
+    cycle: Mutex<Cycle>,
+    mode: watch::Sender<Mode>,
+    /// How often `mode_periodic` is called, if modes are enabled at all.
+    mode_period: Mutex<Option<Duration>>,
+    /// Set once the system has finished stopping.
+    finished: watch::Sender<Option<ShutdownReason>>,
+    events: broadcast::Sender<LifecycleEvent>,
+}
+
+/// The run cycle the system is in. See
+/// [`System::start`](crate::system::System::start).
+#[derive(Debug, Default)]
+struct Cycle {
+    /// Counts from 1, or 0 before the first cycle.
+    number: u64,
+    /// Ends this cycle, without shutting down the whole system.
+    shutdown: ShutdownManager,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct NodeRecord {
+    pub name: String,
+    pub type_id: TypeId,
+    pub ready: bool,
+    pub state: NodeState,
+    /// Whether someone asked for the node to be paused.
+    pub pause_requested: bool,
+    /// The mode the node has entered, and not exited yet.
+    pub mode: Option<Mode>,
+    /// Whether someone asked for the node to be restarted.
+    pub restart_requested: bool,
+    pub options: NodeOptions,
+    pub ticks: TickStats,
+    pub last_heartbeat: Instant,
+}
+
+impl Registry {
+    pub fn register(&self, id: NodeId, name: String, type_id: TypeId, options: NodeOptions) {
+        self.nodes().insert(
+            id,
+            NodeRecord {
+                name,
+                type_id,
+                ready: false,
+                state: NodeState::Starting,
+                pause_requested: false,
+                mode: None,
+                restart_requested: false,
+                options,
+                ticks: TickStats::default(),
+                last_heartbeat: Instant::now(),
+            },
+        );
+        self.changed.send_replace(());
+    }
+
+    pub fn set_ready(&self, id: NodeId) {
+        let became_ready = self.nodes().get_mut(&id).map_or(false, |record| {
+            if record.state == NodeState::Starting {
+                record.state = NodeState::Ready;
+            }
+
+            !std::mem::replace(&mut record.ready, true)
+        });
+
+        if became_ready {
+            self.changed.send_replace(());
+            self.event(id, NodeEvent::Ready);
+        }
+    }
+
+    pub fn set_failed(&self, id: NodeId, message: String) {
+        self.set_state(id, NodeState::Failed);
+        self.event(id, NodeEvent::Failed(message));
+    }
+
+    pub fn set_state(&self, id: NodeId, state: NodeState) {
+        if let Some(record) = self.nodes().get_mut(&id) {
+            record.state = state;
+        }
+        self.changed.send_replace(());
+    }
+
+    pub fn state(&self, id: NodeId) -> Option<NodeState> {
+        self.nodes().get(&id).map(|record| record.state)
+    }
+
+    pub fn name(&self, id: NodeId) -> Option<String> {
+        self.nodes().get(&id).map(|record| record.name.clone())
+    }
+
+    pub fn is_failed(&self, id: NodeId) -> bool {
+        self.state(id) == Some(NodeState::Failed)
+    }
+
+    /// Ask for a node to be paused, or resumed. Returns `false` if there is no
+    /// such node.
+    pub fn request_pause(&self, id: NodeId, paused: bool) -> bool {
+        let found = match self.nodes().get_mut(&id) {
+            Some(record) => {
+                record.pause_requested = paused;
+                true
+            }
+            None => false,
+        };
+        self.changed.send_replace(());
+
+        found
+    }
+
+    /// Wait until a pause (or, with `false`, a resume) of the node is
+    /// requested.
+    pub async fn wait_pause_requested(&self, id: NodeId, paused: bool) {
+        self.wait_until(|nodes| {
+            nodes
+                .get(&id)
+                .map_or(false, |record| record.pause_requested == paused)
+        })
+        .await
+    }
+
+    pub fn schedule(&self, id: NodeId) -> Option<Schedule> {
+        self.nodes()
+            .get(&id)
+            .and_then(|record| record.options.schedule)
+    }
+
+    pub fn watchdog(&self, id: NodeId) -> Option<Watchdog> {
+        self.nodes()
+            .get(&id)
+            .and_then(|record| record.options.watchdog)
+    }
+
+    /// Record that the node is alive. This doesn't wake any waiters, since it
+    /// is polled instead.
+    pub fn heartbeat(&self, id: NodeId) {
+        if let Some(record) = self.nodes().get_mut(&id) {
+            record.last_heartbeat = Instant::now();
+        }
+    }
+
+    pub fn last_heartbeat(&self, id: NodeId) -> Option<Instant> {
+        self.nodes().get(&id).map(|record| record.last_heartbeat)
+    }
+
+    /// Ask for a node to be restarted.
+    pub fn request_restart(&self, id: NodeId) {
+        if let Some(record) = self.nodes().get_mut(&id) {
+            record.restart_requested = true;
+        }
+        self.changed.send_replace(());
+    }
+
+    /// Wait until a restart of the node is requested, then clear the request.
+    pub async fn take_restart_request(&self, id: NodeId) {
+        self.wait_until(|nodes| {
+            nodes
+                .get(&id)
+                .map_or(false, |record| record.restart_requested)
+        })
+        .await;
+
+        if let Some(record) = self.nodes().get_mut(&id) {
+            record.restart_requested = false;
+        }
+    }
+
+    /// Record that a periodic node ticked, `jitter` late, for `duration`.
+    pub fn record_tick(&self, id: NodeId, jitter: Duration, duration: Duration) {
+        if let Some(record) = self.nodes().get_mut(&id) {
+            if let Some(schedule) = record.options.schedule {
+                record.ticks.record(schedule.period, jitter, duration);
+            }
+        }
+    }
+
+    /// Broadcast something that happened to a node.
+    pub fn event(&self, id: NodeId, event: NodeEvent) {
+        let name = match self.nodes().get(&id) {
+            Some(record) => record.name.clone(),
+            None => return,
+        };
+
+        // Nobody may be subscribed, which is fine.
+        let _ = self.events.send(LifecycleEvent::Node { id, name, event });
+    }
+
+    pub fn phase(&self) -> SystemState {
+        *self.phase.borrow()
+    }
+
+    pub fn set_phase(&self, phase: SystemState) {
+        self.phase.send_replace(phase);
+        let _ = self.events.send(LifecycleEvent::System(phase));
+    }
+
+    /// Broadcast a [`LifecycleEvent::Reload`].
+    pub fn reload(&self) {
+        let _ = self.events.send(LifecycleEvent::Reload);
+    }
+
+    /// Start a new run cycle, ended by `shutdown`, and return its number.
+    ///
+    /// Only the nodes in `keep` take part again: their records are reset, and
+    /// every other record (i.e. nodes spawned in the last cycle) is dropped.
+    pub fn begin_cycle(&self, shutdown: ShutdownManager, keep: &[NodeId]) -> u64 {
+        let number = {
+            let mut cycle = self.cycle();
+            cycle.number += 1;
+            cycle.shutdown = shutdown;
+            cycle.number
+        };
+
+        self.nodes().retain(|id, record| {
+            record.ready = false;
+            record.state = NodeState::Starting;
+            record.pause_requested = false;
+            record.mode = None;
+            record.restart_requested = false;
+            record.ticks = TickStats::default();
+            keep.contains(id)
+        });
+        self.finished.send_replace(None);
+        self.changed.send_replace(());
+
+        if number > 1 {
+            for &id in keep {
+                self.event(id, NodeEvent::Restarted);
+            }
+        }
+
+        number
+    }
+
+    /// The number of the current run cycle, counting from 1, or 0 if the
+    /// system hasn't started yet.
+    pub fn cycle_number(&self) -> u64 {
+        self.cycle().number
+    }
+
+    /// End the current run cycle, leaving the system idle.
+    pub fn end_cycle(&self, reason: ShutdownReason) {
+        self.cycle().shutdown.shutdown_with(reason)
+    }
+
+    pub fn enable_modes(&self, config: ModeConfig) {
+        *self.mode_period.lock().unwrap_or_else(|e| e.into_inner()) = Some(config.period);
+        self.set_mode(config.initial);
+    }
+
+    pub fn mode_period(&self) -> Option<Duration> {
+        *self.mode_period.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn mode(&self) -> Mode {
+        *self.mode.borrow()
+    }
+
+    /// Switch the system to another mode. Switching to the current mode does
+    /// nothing.
+    pub fn set_mode(&self, mode: Mode) {
+        if self.mode.send_replace(mode) != mode {
+            let _ = self.events.send(LifecycleEvent::Mode(mode));
+        }
+    }
+
+    /// Record which mode the node is in, as far as its mode hooks know.
+    pub fn set_node_mode(&self, id: NodeId, mode: Option<Mode>) {
+        if let Some(record) = self.nodes().get_mut(&id) {
+            record.mode = mode;
+        }
+    }
+
+    /// Take the mode the node has entered and not exited yet, if any.
+    pub fn take_node_mode(&self, id: NodeId) -> Option<Mode> {
+        self.nodes()
+            .get_mut(&id)
+            .and_then(|record| record.mode.take())
+    }
+
+    pub fn watch_mode(&self) -> watch::Receiver<Mode> {
+        self.mode.subscribe()
+    }
+
+    pub fn set_finished(&self, reason: ShutdownReason) {
+        self.finished.send_replace(Some(reason));
+    }
+
+    /// Wait until the system has finished stopping, and return why it stopped.
+    /// If it is restarted, this waits for the new run cycle to stop instead.
+    pub async fn wait_finished(&self) -> ShutdownReason {
+        let mut finished = self.finished.subscribe();
+
+        loop {
+            if let Some(reason) = finished.borrow().clone() {
+                return reason;
+            }
+
+            // We hold the sender, so this can't fail.
+            let _ = finished.changed().await;
+        }
+    }
+
+    pub fn info(&self, id: NodeId) -> Option<NodeInfo> {
+        self.nodes().get(&id).map(|record| record.info(id))
+    }
+
+    pub fn infos(&self) -> Vec<NodeInfo> {
+        self.nodes()
+            .iter()
+            .map(|(&id, record)| record.info(id))
+            .collect()
+    }
+
+    pub fn subscribe(&self) -> EventStream {
+        EventStream::new(self.events.subscribe())
+    }
+
+    pub fn is_ready(&self, id: NodeId) -> bool {
+        self.nodes().get(&id).map_or(false, |record| record.ready)
+    }
+
+    /// The names of the given nodes that aren't ready yet.
+    pub fn not_ready<'a>(&self, ids: impl IntoIterator<Item = &'a NodeId>) -> Vec<String> {
+        let nodes = self.nodes();
+
+        ids.into_iter()
+            .filter_map(|id| nodes.get(id))
+            .filter(|record| !record.ready)
+            .map(|record| record.name.clone())
+            .collect()
+    }
+
+    /// Wait until every one of the given nodes is ready.
+    pub async fn wait_ready(&self, ids: &[NodeId]) {
+        self.wait_until(|nodes| {
+            ids.iter()
+                .all(|id| nodes.get(id).map_or(false, |record| record.ready))
+        })
+        .await
+    }
+
+    /// Wait until there is at least one node matching `filter`, and every such
+    /// node is ready.
+    pub async fn wait_ready_matching(&self, filter: impl Fn(&NodeRecord) -> bool) {
+        self.wait_until(|nodes| {
+            let mut matching = nodes.values().filter(|record| filter(record)).peekable();
+            matching.peek().is_some() && matching.all(|record| record.ready)
+        })
+        .await
+    }
+
+    /// Wait until `condition` holds for the records.
+    async fn wait_until(&self, condition: impl Fn(&BTreeMap<NodeId, NodeRecord>) -> bool) {
+        // Subscribe before checking, so we can't miss a change in between.
+        let mut changed = self.changed.subscribe();
+
+        loop {
+            if condition(&self.nodes()) {
+                return;
+            }
+
+            // We hold the sender, so this can't fail.
+            let _ = changed.changed().await;
+        }
+    }
+
+    fn nodes(&self) -> MutexGuard<'_, BTreeMap<NodeId, NodeRecord>> {
+        self.nodes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn cycle(&self) -> MutexGuard<'_, Cycle> {
+        self.cycle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl NodeRecord {
+    fn info(&self, id: NodeId) -> NodeInfo {
+        NodeInfo {
+            id,
+            name: self.name.clone(),
+            ready: self.ready,
+            state: self.state,
+            ticks: self.options.schedule.map(|_| self.ticks),
+        }
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self {
+            nodes: Mutex::default(),
+            changed: watch::channel(()).0,
+            phase: watch::channel(SystemState::default()).0,
+            cycle: Mutex::default(),
+            mode: watch::channel(Mode::default()).0,
+            mode_period: Mutex::default(),
+            finished: watch::channel(None).0,
+            events: broadcast::channel(event::CAPACITY).0,
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Opt-in OS signal handling. See [`System::handle_signals`].
+//!
+//! The first SIGINT or SIGTERM starts a graceful shutdown with
+//! [`ShutdownReason::Signal`], so every node's `stopping` hook still runs. A
+//! second signal exits the process immediately, for when a `stopping` hook
+//! hangs. SIGHUP is treated like SIGTERM, unless reloading is enabled, in which
+//! case every SIGHUP broadcasts a [`LifecycleEvent::Reload`] instead, which
+//! every subscriber to the system's events receives, including nodes in
+//! subsystems.
+//!
+//! Signal handlers can't be unregistered, so one listener serves every system
+//! in the process, and keeps listening once they stop. A signal arriving while
+//! no system handles signals exits the process, as it would have without a
+//! handler.
+//!
+//! [`System::handle_signals`]: crate::system::System::handle_signals
+//! [`LifecycleEvent::Reload`]: crate::event::LifecycleEvent::Reload
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use lazy_static::lazy_static;
+use mekena_util::shutdown::{ShutdownManager, ShutdownReason};
+use tokio::task::JoinHandle;
+
+use crate::registry::Registry;
+
+pub const SIGHUP: i32 = 1;
+pub const SIGINT: i32 = 2;
+pub const SIGTERM: i32 = 15;
+
+/// Which signals the system reacts to, and how.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SignalConfig {
+    reload_on_hangup: bool,
+}
+
+impl SignalConfig {
+    /// Construct the default config: SIGINT, SIGTERM and SIGHUP all shut down.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Broadcast a [`LifecycleEvent::Reload`](crate::event::LifecycleEvent::Reload)
+    /// on SIGHUP, instead of shutting down.
+    pub fn reload_on_hangup(mut self, reload: bool) -> Self {
+        self.reload_on_hangup = reload;
+        self
+    }
+}
+
+lazy_static! {
+    /// The systems currently handling signals. Signal handlers can't be
+    /// unregistered once registered, so a single listener serves every system
+    /// for the rest of the process.
+    static ref LISTENER: Mutex<Listener> = Mutex::default();
+}
+
+#[derive(Default)]
+struct Listener {
+    task: Option<JoinHandle<()>>,
+    subscribers: Vec<Subscriber>,
+    next_id: u64,
+}
+
+struct Subscriber {
+    id: u64,
+    config: SignalConfig,
+    shutdown: ShutdownManager,
+    registry: Arc<Registry>,
+    /// Whether a signal has already started shutting the system down.
+    received: bool,
+}
+
+/// Handles signals for a system until dropped, from [`subscribe`].
+#[derive(Debug)]
+pub(crate) struct Subscription {
+    id: u64,
+}
+
+/// Handle signals for a system, starting the listener if it isn't running.
+pub(crate) fn subscribe(
+    config: SignalConfig,
+    shutdown: ShutdownManager,
+    registry: Arc<Registry>,
+) -> std::io::Result<Subscription> {
+    let mut listener = lock();
+
+    // The listener stops with the runtime it was spawned on.
+    if listener.task.as_ref().map_or(true, JoinHandle::is_finished) {
+        let mut signals = Signals::new()?;
+        listener.task = Some(tokio::spawn(async move {
+            loop {
+                let signal = signals.recv().await;
+                lock().dispatch(signal);
+            }
+        }));
+    }
+
+    let id = listener.next_id;
+    listener.next_id += 1;
+    listener.subscribers.push(Subscriber {
+        id,
+        config,
+        shutdown,
+        registry,
+        received: false,
+    });
+
+    Ok(Subscription { id })
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        lock()
+            .subscribers
+            .retain(|subscriber| subscriber.id != self.id);
+    }
+}
+
+impl Listener {
+    fn dispatch(&mut self, signal: i32) {
+        // With no system to stop, do what the signal would have done had it
+        // not been handled.
+        if self.subscribers.is_empty() {
+            exit(signal);
+        }
+
+        for subscriber in &mut self.subscribers {
+            if signal == SIGHUP && subscriber.config.reload_on_hangup {
+                subscriber.registry.reload();
+                continue;
+            }
+
+            if subscriber.received {
+                exit(signal);
+            }
+
+            subscriber.received = true;
+            subscriber
+                .shutdown
+                .shutdown_with(ShutdownReason::Signal(signal));
+        }
+    }
+}
+
+fn lock() -> MutexGuard<'static, Listener> {
+    LISTENER.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn exit(signal: i32) -> ! {
+    std::process::exit(ShutdownReason::Signal(signal).exit_code().into())
+}
+
+#[cfg(unix)]
+struct Signals {
+    interrupt: tokio::signal::unix::Signal,
+    terminate: tokio::signal::unix::Signal,
+    hangup: tokio::signal::unix::Signal,
+}
+
+#[cfg(unix)]
+impl Signals {
+    fn new() -> std::io::Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        Ok(Self {
+            interrupt: signal(SignalKind::interrupt())?,
+            terminate: signal(SignalKind::terminate())?,
+            hangup: signal(SignalKind::hangup())?,
+        })
+    }
+
+    async fn recv(&mut self) -> i32 {
+        tokio::select! {
+            _ = self.interrupt.recv() => SIGINT,
+            _ = self.terminate.recv() => SIGTERM,
+            _ = self.hangup.recv() => SIGHUP,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct Signals;
+
+#[cfg(not(unix))]
+impl Signals {
+    fn new() -> std::io::Result<Self> {
+        Ok(Self)
+    }
+
+    async fn recv(&mut self) -> i32 {
+        // Only Ctrl-C is available here. If we can't listen for it, never
+        // return rather than spinning.
+        match tokio::signal::ctrl_c().await {
+            Ok(()) => SIGINT,
+            Err(_) => std::future::pending().await,
+        }
+    }
+}
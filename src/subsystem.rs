@@ -0,0 +1,193 @@
+//! Composite nodes, which group child nodes into one unit.
+//!
+//! A [`Subsystem`] is added to a system like any other node, and its children
+//! follow it through every phase: they start while it starts, run while it
+//! runs, are paused (in reverse order) and resumed with it, and stop (in
+//! reverse order) while it stops. The subsystem is only ready once all of its
+//! children are.
+//!
+//! Children share a mailbox and state of their own, separate from the rest of
+//! the system's. Use [`Context::send_outer`] to message the system outside.
+//! They still show up in the system's node listings and events. A child
+//! failing stops its siblings and fails the subsystem, and so the whole
+//! system, as any other node would.
+
+use std::any::TypeId;
+
+use futures::future::try_join_all;
+
+use crate::{
+    context::Context,
+    node::{Node, NodeConfig, NodeId, NodeOptions, NodeState},
+    system::{self, SystemError},
+};
+
+/// A node made of other nodes. See the [module docs](self).
+#[derive(Default)]
+pub struct Subsystem {
+    children: Vec<Child>,
+    /// The context the children are spawned from, which owns their mailbox
+    /// and state. Created on the first start, and kept across run cycles.
+    ctx: Option<Context>,
+}
+
+struct Child {
+    node: Box<dyn Node + 'static>,
+    name: String,
+    type_id: TypeId,
+    options: NodeOptions,
+    ctx: Option<Context>,
+}
+
+impl Subsystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a child node.
+    pub fn add_node<N: Node + 'static>(self, node: N) -> Self {
+        self.add_node_with(node, NodeConfig::default())
+    }
+
+    /// Add a child node with a name, a period or a watchdog. Only those are
+    /// taken from `config`: children start together, and are ready as soon as
+    /// `starting` returns.
+    pub fn add_node_with<N: Node + 'static>(mut self, node: N, config: NodeConfig) -> Self {
+        let options = config.options();
+        self.children.push(Child {
+            node: Box::new(node),
+            name: config
+                .name
+                .unwrap_or_else(|| std::any::type_name::<N>().to_owned()),
+            type_id: TypeId::of::<N>(),
+            options,
+            ctx: None,
+        });
+        self
+    }
+
+    /// The IDs of the children, once the subsystem has started.
+    pub fn children(&self) -> Vec<NodeId> {
+        self.children
+            .iter()
+            .filter_map(|child| child.ctx.as_ref()?.node_id())
+            .collect()
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Node for Subsystem {
+    async fn starting(&mut self, ctx: &Context) {
+        let scoped = self.ctx.get_or_insert_with(|| ctx.scoped());
+        scoped.rescope(ctx.scope());
+
+        let registry = ctx.registry();
+        for child in &mut self.children {
+            let child_ctx = child.ctx.get_or_insert_with(|| scoped.for_node());
+            child_ctx.rescope(scoped.scope());
+
+            // Registering again resets the record, which a new run cycle drops.
+            let id = child_ctx
+                .node_id()
+                .expect("node contexts always have an ID");
+            registry.register(id, child.name.clone(), child.type_id, child.options);
+        }
+
+        let result = try_join_all(self.children.iter_mut().map(|child| {
+            let ctx = child
+                .ctx
+                .as_ref()
+                .expect("children have a context once started");
+            system::start_child(&mut *child.node, ctx)
+        }))
+        .await;
+
+        self.fail(ctx, result).await;
+    }
+
+    async fn running(&mut self, ctx: &Context) {
+        let result = try_join_all(self.children.iter_mut().map(|child| {
+            let ctx = child
+                .ctx
+                .as_ref()
+                .expect("children have a context once started");
+            system::run(&mut *child.node, ctx)
+        }))
+        .await;
+
+        self.fail(ctx, result).await;
+    }
+
+    async fn pause(&mut self, ctx: &Context) {
+        let registry = ctx.registry();
+
+        let result = async {
+            for (node, child_ctx) in self.started().rev() {
+                let id = child_ctx
+                    .node_id()
+                    .expect("node contexts always have an ID");
+
+                if let Some(NodeState::Ready | NodeState::Running) = registry.state(id) {
+                    system::pause_node(node, child_ctx).await?;
+                }
+            }
+
+            Ok(())
+        }
+        .await;
+
+        self.fail(ctx, result).await;
+    }
+
+    async fn resume(&mut self, ctx: &Context) {
+        let registry = ctx.registry();
+
+        let result = async {
+            for (node, child_ctx) in self.started() {
+                let id = child_ctx
+                    .node_id()
+                    .expect("node contexts always have an ID");
+
+                if let Some(NodeState::Paused) = registry.state(id) {
+                    system::resume_node(node, child_ctx).await?;
+                }
+            }
+
+            Ok(())
+        }
+        .await;
+
+        self.fail(ctx, result).await;
+    }
+
+    async fn stopping(&mut self, _ctx: &Context) {
+        self.stop_children().await;
+    }
+}
+
+impl Subsystem {
+    /// The children that have a context, i.e. every child once the subsystem
+    /// has started.
+    fn started(&mut self) -> impl DoubleEndedIterator<Item = (&mut dyn Node, &Context)> {
+        self.children
+            .iter_mut()
+            .filter_map(|child| Some((&mut *child.node as &mut dyn Node, child.ctx.as_ref()?)))
+    }
+
+    /// Stop the children in reverse order, skipping those that failed or
+    /// never finished starting.
+    async fn stop_children(&mut self) {
+        for (node, child_ctx) in self.started().rev() {
+            system::stop_child(node, child_ctx).await;
+        }
+    }
+
+    /// If a child failed, stop the others, then fail the subsystem with the
+    /// child's error.
+    async fn fail<T>(&mut self, ctx: &Context, result: Result<T, SystemError>) {
+        if let Err(e) = result {
+            self.stop_children().await;
+            ctx.fail(e);
+        }
+    }
+}
@@ -1,20 +1,67 @@
-use tokio::select;
+use std::{
+    any::{Any, TypeId},
+    convert::Infallible,
+    future::Future,
+    panic::AssertUnwindSafe,
+    process::{ExitCode, Termination},
+    time::Duration,
+};
 
-use crate::{context::Context, node::Node};
+use futures::{future::LocalBoxFuture, stream::FuturesUnordered, FutureExt, StreamExt};
+use mekena_util::shutdown::{ShutdownManager, ShutdownReason};
+use tokio::{select, sync::watch, time::Instant};
+
+use crate::{
+    context::{Context, Local},
+    dependency::{self, Declaration},
+    event::NodeEvent,
+    handle::SystemHandle,
+    mode::{Mode, ModeConfig},
+    node::{Dependency, Node, NodeConfig, NodeId, NodeState},
+    periodic::Schedule,
+    signal::{SignalConfig, Subscription},
+    watchdog::{self, Miss},
+    wiring::{Group, Nodes},
+};
 
 pub struct System {
-    state: SystemState,
-    nodes: Vec<Box<dyn Node + 'static>>, // TODO: can we figure this out at compile time?
+    nodes: Vec<NodeEntry>, // TODO: can we figure this out at compile time?
+    /// Indices into `nodes`, grouped so that every node's dependencies are in
+    /// an earlier level. Computed when the system starts.
+    levels: Vec<Vec<usize>>,
     context: Context,
+    signals: Option<SignalConfig>,
+    startup_timeout: Option<Duration>,
+    /// Nodes added at runtime through [`Context::spawn_node`], each running its
+    /// whole lifecycle.
+    spawned: FuturesUnordered<LocalBoxFuture<'static, ()>>,
+}
+
+/// A node registered before the system started, along with its context.
+struct NodeEntry {
+    node: Box<dyn Node + 'static>,
+    ctx: Context,
+    id: NodeId,
+    name: String,
+    type_id: TypeId,
+    dependencies: Vec<Dependency>,
+    /// `dependencies`, resolved when the system starts.
+    depends_on: Vec<NodeId>,
+    manual_ready: bool,
+    startup_timeout: Option<Duration>,
 }
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub enum SystemState {
     #[default]
     NotStarted,
     Starting,
     Running,
     Stopping,
+    /// A run cycle has ended, but the system can be started again.
+    Idle,
+    /// The system has shut down for good.
+    Stopped,
 }
 
 pub enum NextState {
@@ -25,85 +72,853 @@ pub enum NextState {
 impl System {
     pub fn new() -> Self {
         Self {
-            state: SystemState::default(),
             nodes: Vec::new(),
+            levels: Vec::new(),
             context: Context::new(),
+            signals: None,
+            startup_timeout: None,
+            spawned: FuturesUnordered::new(),
         }
     }
 
+    /// Register a singleton resource, which nodes get with
+    /// [`Context::resource`] and [`Context::resource_mut`]. There is at most
+    /// one resource of each type: adding another replaces it.
+    pub fn add_state<T: Send + Sync + 'static>(self, value: T) -> Self {
+        self.context.shared().resources.insert(value);
+        self
+    }
+
     /// Register a node.
-    pub fn add_node(mut self, node: impl Node + 'static) -> Self {
-        self.nodes.push(Box::new(node));
+    pub fn add_node<N: Node + 'static>(self, node: N) -> Self {
+        self.add_node_with(node, NodeConfig::default())
+    }
+
+    /// Register a node with a name and dependencies. Nodes start in dependency
+    /// order, with independent nodes starting in parallel, and stop in the
+    /// reverse order.
+    pub fn add_node_with<N: Node + 'static>(mut self, node: N, config: NodeConfig) -> Self {
+        let ctx = self.context.for_node();
+        let id = ctx.node_id().expect("node contexts always have an ID");
+        let options = config.options();
+        let name = config
+            .name
+            .unwrap_or_else(|| std::any::type_name::<N>().to_owned());
+
+        self.context
+            .registry()
+            .register(id, name.clone(), TypeId::of::<N>(), options);
+        self.nodes.push(NodeEntry {
+            node: Box::new(node),
+            ctx,
+            id,
+            name,
+            type_id: TypeId::of::<N>(),
+            dependencies: config.dependencies,
+            depends_on: Vec::new(),
+            manual_ready: config.manual_ready,
+            startup_timeout: config.startup_timeout,
+        });
         self
     }
 
-    pub async fn start(&mut self) -> Result<(), SystemError> {
-        match self.starting().await? {
-            NextState::Continue => match self.running().await? {
-                _ => self.stopping().await?,
-            },
-            NextState::Stop => self.stopping().await?,
+    /// Register a statically typed group of nodes, usually built with
+    /// [`system!`](crate::system!), which checks at compile time that every
+    /// message the group's nodes receive is sent by one of them, or is declared
+    /// external.
+    pub fn add_group<T: Nodes + 'static, E: 'static>(self, group: Group<T, E>) -> Self
+    where
+        Group<T, E>: Node,
+    {
+        let names: Vec<_> = T::types().into_iter().map(|(name, _)| name).collect();
+        let name = format!("group({})", names.join(", "));
+        self.add_node_with(group, NodeConfig::new().name(name))
+    }
+
+    /// Fail the startup if every node isn't ready within `timeout` of the
+    /// system starting. Individual nodes can have their own timeouts too, see
+    /// [`NodeConfig::startup_timeout`].
+    pub fn startup_timeout(self, timeout: Duration) -> Self {
+        Self {
+            startup_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Shut down gracefully on SIGINT, SIGTERM or SIGHUP, and exit immediately
+    /// on a second signal. See [`crate::signal`] for details.
+    pub fn handle_signals(self, config: SignalConfig) -> Self {
+        Self {
+            signals: Some(config),
+            ..self
+        }
+    }
+
+    /// Enable operating modes, and drive every node's mode hooks once its
+    /// `running` hook returns. See [`crate::mode`] for details.
+    ///
+    /// Since the mode hooks keep going until the system shuts down, a system
+    /// with modes never completes on its own.
+    pub fn modes(self, config: ModeConfig) -> Self {
+        self.context.registry().enable_modes(config);
+        self
+    }
+
+    /// Run the system through one cycle of its starting, running and stopping
+    /// phases. Returns the reason the cycle ended, which is
+    /// [`ShutdownReason::Completed`] if every node finished on its own.
+    ///
+    /// A cycle ended with [`Context::end_cycle`] leaves the system
+    /// [`SystemState::Idle`], and it can be started again with the same nodes
+    /// and state. Nodes can tell which cycle they are in through
+    /// [`Context::cycle`]. A shutdown, or an error, leaves the system
+    /// [`SystemState::Stopped`] for good, and starting it again fails with
+    /// [`SystemError::Shutdown`].
+    pub async fn start(&mut self) -> Result<ShutdownReason, SystemError> {
+        let system = self.context.shutdown_manager();
+        if system.is_shutdown() {
+            return Err(SystemError::Shutdown);
+        }
+
+        let shared = self.context.shared().clone();
+        let registry = &shared.registry;
+        let signals = match self.prepare() {
+            Ok(signals) => signals,
+            Err(e) => {
+                // Nothing has started yet, so there is nothing to stop.
+                let reason = ShutdownReason::from(&e);
+                system.shutdown_with(reason.clone());
+                registry.set_phase(SystemState::Stopped);
+                registry.set_finished(reason);
+                return Err(e);
+            }
         };
 
-        Ok(())
+        let cycle = system.child();
+        let ids: Vec<NodeId> = self.nodes.iter().map(|node| node.id).collect();
+        registry.begin_cycle(cycle.clone(), &ids);
+        self.context.rescope(&cycle);
+        for node in &mut self.nodes {
+            node.ctx.rescope(&cycle);
+        }
+
+        let outcome = self.run().await;
+        match &outcome {
+            Ok(NextState::Continue) => cycle.shutdown_with(ShutdownReason::Completed),
+            Ok(NextState::Stop) => {}
+            Err(e) => system.shutdown_with(e.into()),
+        }
+
+        self.stopping().await?;
+
+        // Signals arriving from here on exit the process, unless another
+        // system handles them.
+        drop(signals);
+
+        let reason = cycle.reason().unwrap_or(ShutdownReason::Completed);
+        registry.set_phase(match system.is_shutdown() {
+            true => SystemState::Stopped,
+            false => SystemState::Idle,
+        });
+        registry.set_finished(reason.clone());
+
+        outcome?;
+
+        Ok(reason)
+    }
+
+    /// Check that no period is zero, order the nodes by their dependencies, and
+    /// start listening for signals if asked to.
+    fn prepare(&mut self) -> Result<Option<Subscription>, SystemError> {
+        let registry = self.context.registry();
+        if registry.mode_period() == Some(Duration::ZERO) {
+            return Err(SystemError::ZeroModePeriod);
+        }
+        for node in &self.nodes {
+            if let Some(schedule) = registry.schedule(node.id) {
+                check_period(&node.name, schedule.period)?;
+            }
+        }
+
+        let ordering = dependency::order(
+            &self
+                .nodes
+                .iter()
+                .map(|node| Declaration {
+                    name: &node.name,
+                    type_id: node.type_id,
+                    dependencies: &node.dependencies,
+                })
+                .collect::<Vec<_>>(),
+        )?;
+
+        let ids: Vec<NodeId> = self.nodes.iter().map(|node| node.id).collect();
+        for (node, dependencies) in self.nodes.iter_mut().zip(ordering.dependencies) {
+            node.depends_on = dependencies.into_iter().map(|i| ids[i]).collect();
+        }
+        self.levels = ordering.levels;
+
+        Ok(self
+            .signals
+            .map(|config| {
+                crate::signal::subscribe(
+                    config,
+                    self.context.shutdown_manager(),
+                    self.context.shared().registry.clone(),
+                )
+            })
+            .transpose()?)
+    }
+
+    /// Start every node, then run them. The system is [`SystemState::Starting`]
+    /// until every node is ready, and [`SystemState::Running`] after that.
+    /// This lasts until every node, including those spawned at runtime, has
+    /// finished running.
+    async fn run(&mut self) -> Result<NextState, SystemError> {
+        let registry = self.context.registry();
+        registry.set_phase(SystemState::Starting);
+
+        let ids: Vec<NodeId> = self.nodes.iter().map(|node| node.id).collect();
+        let local = self.context.local();
+        let spawned = &mut self.spawned;
+
+        let nodes =
+            futures::future::try_join_all(self.nodes.iter_mut().map(|node| run_node(node, &ids)));
+        let nodes = async move {
+            drive(nodes, local, &mut *spawned).await?;
+            drain(local, spawned).await;
+            Ok::<_, SystemError>(())
+        };
+
+        let timeout = self.startup_timeout;
+        let ready = async {
+            let all = registry.wait_ready(&ids);
+            match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, all).await.map_err(|_| {
+                    SystemError::StartupTimeout {
+                        nodes: registry.not_ready(&ids).join(", "),
+                    }
+                }),
+                None => {
+                    all.await;
+                    Ok(())
+                }
+            }
+        };
+
+        tokio::pin!(nodes, ready);
+        let mut is_ready = false;
+
+        loop {
+            select! {
+                result = &mut nodes => {
+                    result?;
+                    return Ok(NextState::Continue);
+                },
+                _ = self.context.await_shutdown() => return Ok(NextState::Stop),
+                result = &mut ready, if !is_ready => {
+                    result?;
+                    is_ready = true;
+                    registry.set_phase(SystemState::Running);
+                },
+            }
+        }
+    }
+
+    /// Run every node's `stopping` hook to completion. Unlike the other
+    /// phases, this is not cut short by a shutdown signal, since a shutdown is
+    /// usually what brought us here.
+    ///
+    /// Nodes spawned at runtime are stopped first, since they may depend on
+    /// the nodes that spawned them. The rest stop in reverse dependency order.
+    /// Nodes that failed are not stopped.
+    async fn stopping(&mut self) -> Result<NextState, SystemError> {
+        let registry = self.context.registry();
+        registry.set_phase(SystemState::Stopping);
+
+        let local = self.context.local();
+        drain(local, &mut self.spawned).await;
+
+        for level in self.levels.iter().rev() {
+            let output = futures::future::join_all(
+                select_mut(&mut self.nodes, level)
+                    .filter(|entry| !registry.is_failed(entry.id))
+                    .map(|NodeEntry { node, ctx, .. }| stop_node(&mut **node, ctx)),
+            );
+            drive(output, local, &mut self.spawned).await;
+        }
+
+        drain(local, &mut self.spawned).await;
+
+        Ok(NextState::Stop)
+    }
+
+    pub fn get_state(&self) -> SystemState {
+        self.context.registry().phase()
+    }
+
+    /// Get a [`SystemHandle`], which can observe the system from outside while
+    /// it runs, e.g. from another thread.
+    pub fn handle(&self) -> SystemHandle {
+        SystemHandle::new(
+            self.context.shared().clone(),
+            self.context.shutdown_manager(),
+        )
+    }
+
+    /// Get an [`ExitStatus`] that reports why the system shut down, once it
+    /// has. This stays valid after the system itself has been consumed.
+    pub fn exit_status(&self) -> ExitStatus {
+        ExitStatus(self.context.shutdown_manager())
+    }
+}
+
+/// Start a node once its dependencies are ready, and run it once every node in
+/// `system` is ready. A node that is still getting ready after `starting`
+/// returns runs straight away instead, so that it can finish.
+async fn run_node(entry: &mut NodeEntry, system: &[NodeId]) -> Result<(), SystemError> {
+    let NodeEntry {
+        node,
+        ctx,
+        id,
+        name,
+        depends_on,
+        manual_ready,
+        startup_timeout,
+        ..
+    } = entry;
+    let registry = ctx.registry();
+
+    registry.wait_ready(depends_on).await;
+
+    let deadline = startup_timeout.map(|timeout| Instant::now() + timeout);
+    let timeout = async {
+        if let Some(deadline) = deadline {
+            tokio::time::timeout_at(deadline, registry.wait_ready(&[*id]))
+                .await
+                .map_err(|_| SystemError::StartupTimeout {
+                    nodes: name.clone(),
+                })?;
+        }
+
+        Ok::<_, SystemError>(())
+    };
+
+    let body = async {
+        hook(ctx, node.starting(ctx)).await?;
+        registry.event(*id, NodeEvent::Started);
+
+        if !*manual_ready {
+            ctx.ready();
+        }
+
+        if ctx.is_ready() {
+            registry.wait_ready(system).await;
+        }
+
+        run(&mut **node, ctx).await
+    };
+
+    select! {
+        result = body => result,
+        Err(e) = timeout => Err(e),
+    }
+}
+
+/// Run a node's `running` hook until it returns, then its `tick` or mode hooks
+/// if it has any. If the node is paused, this is cancelled, and started again
+/// once it resumes. If the node is restarted, it is cancelled too, and the
+/// node's `stopping` and `starting` hooks run before it starts again.
+pub(crate) async fn run<N: Node + ?Sized>(node: &mut N, ctx: &Context) -> Result<(), SystemError> {
+    let id = ctx.node_id().expect("only nodes run hooks");
+    let registry = ctx.registry();
+
+    loop {
+        registry.set_state(id, NodeState::Running);
+        registry.event(id, NodeEvent::Running);
+
+        let running = async {
+            hook(ctx, node.running(ctx)).await?;
+
+            match (registry.schedule(id), registry.mode_period()) {
+                (Some(schedule), _) => run_ticks(node, ctx, schedule).await,
+                (None, Some(period)) => run_modes(node, ctx, period).await,
+                (None, None) => Ok(()),
+            }
+        };
+
+        let restart = select! {
+            result = running => return result,
+            _ = registry.wait_pause_requested(id, true) => false,
+            _ = registry.take_restart_request(id) => true,
+            never = watch_heartbeat(ctx) => match never {},
+        };
+
+        if restart {
+            exit_mode(node, ctx).await?;
+            registry.set_state(id, NodeState::Stopping);
+            hook(ctx, node.stopping(ctx)).await?;
+            registry.set_state(id, NodeState::Starting);
+            hook(ctx, node.starting(ctx)).await?;
+            registry.event(id, NodeEvent::Restarted);
+            continue;
+        }
+
+        pause_node(node, ctx).await?;
+        registry.wait_pause_requested(id, false).await;
+        resume_node(node, ctx).await?;
+    }
+}
+
+/// Run a node's `mode_exit` hook, if it is in a mode, then its `pause` hook.
+/// Its `running` hook must have been cancelled already.
+pub(crate) async fn pause_node<N: Node + ?Sized>(
+    node: &mut N,
+    ctx: &Context,
+) -> Result<(), SystemError> {
+    let id = ctx.node_id().expect("only nodes run hooks");
+    let registry = ctx.registry();
+
+    exit_mode(node, ctx).await?;
+    registry.set_state(id, NodeState::Paused);
+    hook(ctx, node.pause(ctx)).await?;
+    registry.event(id, NodeEvent::Paused);
+
+    Ok(())
+}
+
+/// Run a paused node's `resume` hook. Its `running` hook is started again
+/// separately.
+pub(crate) async fn resume_node<N: Node + ?Sized>(
+    node: &mut N,
+    ctx: &Context,
+) -> Result<(), SystemError> {
+    let id = ctx.node_id().expect("only nodes run hooks");
+
+    hook(ctx, node.resume(ctx)).await?;
+    ctx.registry().event(id, NodeEvent::Resumed);
+
+    Ok(())
+}
+
+/// Call a periodic node's `tick` hook on its schedule, until cancelled.
+async fn run_ticks<N: Node + ?Sized>(
+    node: &mut N,
+    ctx: &Context,
+    schedule: Schedule,
+) -> Result<(), SystemError> {
+    let id = ctx.node_id().expect("only nodes run hooks");
+    // Nodes added to the system are checked before it starts, but not
+    // children of subsystems or nodes spawned at runtime.
+    check_period(
+        &ctx.registry().name(id).unwrap_or_default(),
+        schedule.period,
+    )?;
+
+    let mut interval = tokio::time::interval(schedule.period);
+    interval.set_missed_tick_behavior(schedule.missed.into());
+
+    loop {
+        let due = interval.tick().await;
+        let started = Instant::now();
+
+        iteration(ctx, node.tick(ctx)).await?;
+        ctx.registry()
+            .record_tick(id, started - due, started.elapsed());
     }
+}
+
+/// Reject a node's period if it is zero, which would tick continuously.
+fn check_period(node: &str, period: Duration) -> Result<(), SystemError> {
+    if period.is_zero() {
+        return Err(SystemError::ZeroPeriod {
+            node: node.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+/// Drive a node's mode hooks, following the system from mode to mode, until
+/// cancelled.
+async fn run_modes<N: Node + ?Sized>(
+    node: &mut N,
+    ctx: &Context,
+    period: Duration,
+) -> Result<(), SystemError> {
+    let id = ctx.node_id().expect("only nodes run hooks");
+    let mut modes = ctx.registry().watch_mode();
+    let mut mode: Mode = *modes.borrow_and_update();
+    hook(ctx, node.mode_enter(ctx, mode)).await?;
+    ctx.registry().set_node_mode(id, Some(mode));
+
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        select! {
+            _ = interval.tick() => iteration(ctx, node.mode_periodic(ctx, mode)).await?,
+            Ok(()) = modes.changed() => {
+                let next = *modes.borrow_and_update();
 
-    async fn starting(&mut self) -> Result<NextState, SystemError> {
-        self.state = SystemState::Starting;
+                if next != mode {
+                    ctx.registry().set_node_mode(id, None);
+                    hook(ctx, node.mode_exit(ctx, mode)).await?;
+                    mode = next;
+                    hook(ctx, node.mode_enter(ctx, mode)).await?;
+                    ctx.registry().set_node_mode(id, Some(mode));
+                }
+            },
+        }
+    }
+}
 
-        let output = futures::future::join_all(
-            self.nodes
-                .iter_mut()
-                .map(|x: &mut Box<dyn Node + 'static>| x.starting(&self.context)),
-        );
+/// Run one iteration of a periodic hook. If it runs over the node's budget,
+/// report it to the watchdog as soon as it does, then let it finish.
+async fn iteration<T>(ctx: &Context, future: impl Future<Output = T>) -> Result<T, SystemError> {
+    let id = ctx.node_id().expect("only nodes run hooks");
+    let future = hook(ctx, future);
+    tokio::pin!(future);
 
+    let watchdog = ctx.registry().watchdog(id);
+    if let Some((watchdog, budget)) = watchdog.and_then(|w| Some((w, w.budget?))) {
         select! {
-            _ = output => Ok(NextState::Continue),
-            _ = self.context.await_shutdown() => Ok(NextState::Stop),
+            output = &mut future => return output,
+            _ = tokio::time::sleep(budget) => {
+                watchdog::expire(ctx, &watchdog, Miss::Budget(budget));
+            },
         }
     }
 
-    async fn running(&mut self) -> Result<NextState, SystemError> {
-        self.state = SystemState::Running;
+    future.await
+}
+
+/// Watch a node's heartbeat while it runs, and report to the watchdog each
+/// time it lapses. Never returns.
+async fn watch_heartbeat(ctx: &Context) -> Infallible {
+    let id = ctx.node_id().expect("only nodes run hooks");
+    let registry = ctx.registry();
 
-        let output = futures::future::join_all(
-            self.nodes
-                .iter_mut()
-                .map(|x: &mut Box<dyn Node + 'static>| x.running(&self.context)),
-        );
+    let (watchdog, interval) = match registry.watchdog(id).and_then(|w| Some((w, w.heartbeat?))) {
+        Some(watchdog) => watchdog,
+        None => return std::future::pending().await,
+    };
+
+    // Running counts as the first heartbeat.
+    registry.heartbeat(id);
+    let mut lapsed = false;
+
+    loop {
+        let last = registry.last_heartbeat(id).unwrap_or_else(Instant::now);
+
+        if Instant::now() < last + interval {
+            lapsed = false;
+            tokio::time::sleep_until(last + interval).await;
+            continue;
+        }
+
+        // Only report each lapse once, not once per interval it lasts.
+        if !lapsed {
+            lapsed = true;
+            watchdog::expire(ctx, &watchdog, Miss::Heartbeat(interval));
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Run the `starting` hook of a composite node's child, which is ready as soon
+/// as it returns.
+pub(crate) async fn start_child<N: Node + ?Sized>(
+    node: &mut N,
+    ctx: &Context,
+) -> Result<(), SystemError> {
+    let id = ctx.node_id().expect("only nodes run hooks");
+
+    hook(ctx, node.starting(ctx)).await?;
+    ctx.registry().event(id, NodeEvent::Started);
+    ctx.ready();
+
+    Ok(())
+}
+
+/// Exit the mode a node's mode hooks were cancelled in, if any, so that every
+/// `mode_enter` is matched by a `mode_exit`.
+async fn exit_mode<N: Node + ?Sized>(node: &mut N, ctx: &Context) -> Result<(), SystemError> {
+    let id = ctx.node_id().expect("only nodes run hooks");
+
+    match ctx.registry().take_node_mode(id) {
+        Some(mode) => hook(ctx, node.mode_exit(ctx, mode)).await,
+        None => Ok(()),
+    }
+}
+
+/// Run a node's `mode_exit` hook, if it is in a mode, then its `stopping`
+/// hook.
+pub(crate) async fn stop_node<N: Node + ?Sized>(node: &mut N, ctx: &Context) {
+    let id = ctx.node_id().expect("only nodes run hooks");
+    let registry = ctx.registry();
+
+    registry.set_state(id, NodeState::Stopping);
+
+    // A panic here is already reported as an event, and we're shutting down
+    // anyway.
+    if exit_mode(node, ctx).await.is_err() {
+        return;
+    }
+    if hook(ctx, node.stopping(ctx)).await.is_ok() {
+        registry.set_state(id, NodeState::Stopped);
+        registry.event(id, NodeEvent::Stopped);
+    }
+}
+
+/// Stop a child of a composite node, unless it failed, never finished
+/// starting, or has stopped already.
+pub(crate) async fn stop_child<N: Node + ?Sized>(node: &mut N, ctx: &Context) {
+    let id = ctx.node_id().expect("only nodes run hooks");
+
+    match ctx.registry().state(id) {
+        None | Some(NodeState::Starting | NodeState::Stopped | NodeState::Failed) => {}
+        Some(_) => stop_node(node, ctx).await,
+    }
+}
+
+/// Run one of a node's hooks. If it panics, or fails a composite node (see
+/// [`Context::fail`]), mark the node as failed and return an error instead of
+/// unwinding through the whole system.
+pub(crate) async fn hook<T>(
+    ctx: &Context,
+    future: impl Future<Output = T>,
+) -> Result<T, SystemError> {
+    let id = ctx.node_id().expect("only nodes run hooks");
+    let registry = ctx.registry();
+
+    let output = AssertUnwindSafe(future)
+        .catch_unwind()
+        .await
+        .map_err(|panic| {
+            let message = panic_message(&*panic);
+            registry.set_failed(id, message.clone());
+
+            SystemError::NodeFailed {
+                node: registry.name(id).unwrap_or_default(),
+                message,
+            }
+        })?;
+
+    match ctx.take_failure() {
+        Some(error) => {
+            registry.set_failed(id, error.to_string());
+            Err(error)
+        }
+        None => Ok(output),
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_owned()
+    }
+}
+
+/// Mutably borrow the nodes at the given indices.
+fn select_mut<'a>(
+    nodes: &'a mut [NodeEntry],
+    indices: &'a [usize],
+) -> impl Iterator<Item = &'a mut NodeEntry> {
+    nodes
+        .iter_mut()
+        .enumerate()
+        .filter(move |(i, _)| indices.contains(i))
+        .map(|(_, node)| node)
+}
+
+/// Run a node spawned at runtime through its whole lifecycle, then mark it as
+/// finished.
+pub(crate) async fn lifecycle(
+    mut node: Box<dyn Node + 'static>,
+    ctx: Context,
+    done: watch::Sender<bool>,
+) {
+    let id = ctx.node_id().expect("node contexts always have an ID");
+    let registry = ctx.registry();
+
+    let result = async {
+        let started = select! {
+            result = hook(&ctx, node.starting(&ctx)) => {
+                result?;
+                true
+            },
+            _ = ctx.await_shutdown() => false,
+        };
+
+        if started {
+            registry.event(id, NodeEvent::Started);
+            ctx.ready();
+
+            select! {
+                result = run(&mut *node, &ctx) => result?,
+                _ = ctx.await_shutdown() => {},
+            }
+        }
+
+        Ok::<_, SystemError>(started)
+    }
+    .await;
+
+    match result {
+        // A node stopped before it finished starting has nothing to stop.
+        Ok(true) => stop_node(&mut *node, &ctx).await,
+        Ok(false) => registry.set_state(id, NodeState::Stopped),
+        Err(e) => ctx.shutdown_with(ShutdownReason::error(e)),
+    }
+
+    // Nobody may be waiting on the handle, which is fine.
+    let _ = done.send(true);
+}
+
+/// Poll `future` to completion, while also polling every node spawned at
+/// runtime.
+async fn drive<F: Future>(
+    future: F,
+    local: &Local,
+    spawned: &mut FuturesUnordered<LocalBoxFuture<'static, ()>>,
+) -> F::Output {
+    tokio::pin!(future);
+
+    loop {
+        spawned.extend(local.take_spawned());
 
         select! {
-            _ = output => Ok(NextState::Continue),
-            _ = self.context.await_shutdown() => Ok(NextState::Stop),
+            output = &mut future => return output,
+            _ = local.notified() => {},
+            Some(()) = spawned.next(), if !spawned.is_empty() => {},
         }
     }
+}
 
-    async fn stopping(&mut self) -> Result<NextState, SystemError> {
-        self.state = SystemState::Stopping;
+/// Poll every node spawned at runtime until they have all finished.
+async fn drain(local: &Local, spawned: &mut FuturesUnordered<LocalBoxFuture<'static, ()>>) {
+    loop {
+        spawned.extend(local.take_spawned());
 
-        let output = futures::future::join_all(
-            self.nodes
-                .iter_mut()
-                .map(|x: &mut Box<dyn Node + 'static>| x.stopping(&self.context)),
-        );
+        if spawned.is_empty() {
+            return;
+        }
 
         select! {
-            _ = output => Ok(NextState::Continue),
-            _ = self.context.await_shutdown() => Ok(NextState::Stop),
+            _ = local.notified() => {},
+            _ = spawned.next() => {},
         }
     }
+}
 
-    pub fn get_state(&self) -> SystemState {
-        self.state
+/// Reports why a [`System`] shut down, and which process exit code that maps
+/// to. Obtained through [`System::exit_status`].
+#[derive(Clone, Debug)]
+pub struct ExitStatus(ShutdownManager);
+
+impl ExitStatus {
+    /// The reason the system shut down, if it has.
+    pub fn reason(&self) -> Option<ShutdownReason> {
+        self.0.reason()
+    }
+
+    /// The exit code of the shutdown reason, or `0` if there was none.
+    pub fn exit_code(&self) -> u8 {
+        self.reason().map_or(0, |r| r.exit_code())
+    }
+
+    /// Combine the output of `main` with the shutdown reason. The output is
+    /// always reported (so errors are printed), but a non-zero exit code from
+    /// the shutdown reason takes precedence.
+    pub fn report(self, output: impl Termination) -> ExitCode {
+        let reported = output.report();
+
+        match self.exit_code() {
+            0 => reported,
+            code => ExitCode::from(code),
+        }
+    }
+}
+
+impl From<&SystemError> for ShutdownReason {
+    fn from(error: &SystemError) -> Self {
+        match error {
+            SystemError::StartupTimeout { .. } => ShutdownReason::Timeout,
+            e => ShutdownReason::error(e),
+        }
+    }
+}
+
+impl Default for System {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[derive(thiserror::Error, miette::Diagnostic, Debug)]
 pub enum SystemError {
-    #[error("The context was commanded to shut down.")]
-    #[diagnostic(code(mekena::system::shutdown))]
+    #[error("The system has already shut down.")]
+    #[diagnostic(
+        code(mekena::system::shutdown),
+        help("Use `Context::end_cycle` instead of `Context::shutdown` to be able to start the system again.")
+    )]
     Shutdown,
 
+    #[error("Node `{node}` depends on {dependency}, but no such node was registered.")]
+    #[diagnostic(
+        code(mekena::system::missing_dependency),
+        help("Register the dependency too, or remove it from the node's `NodeConfig`.")
+    )]
+    MissingDependency { node: String, dependency: String },
+
+    #[error("Node dependencies form a cycle: {cycle}")]
+    #[diagnostic(
+        code(mekena::system::dependency_cycle),
+        help("Each node depends on the one after it, so none of them can start first. Remove one of these dependencies.")
+    )]
+    DependencyCycle { cycle: String },
+
+    #[error("The mode hooks have a period of zero.")]
+    #[diagnostic(
+        code(mekena::system::zero_mode_period),
+        help("The mode hooks can't run continuously. Give them a period of at least a millisecond in the `ModeConfig`.")
+    )]
+    ZeroModePeriod,
+
+    #[error("Node `{node}` has a period of zero.")]
+    #[diagnostic(
+        code(mekena::system::zero_period),
+        help("A node can't tick continuously. Give it a period of at least a millisecond in its `NodeConfig`.")
+    )]
+    ZeroPeriod { node: String },
+
+    #[error("Timed out waiting for {nodes} to become ready.")]
+    #[diagnostic(
+        code(mekena::system::startup_timeout),
+        help("Nodes registered with `NodeConfig::manual_ready` must call `Context::ready`.")
+    )]
+    StartupTimeout { nodes: String },
+
+    #[error("Node `{node}` failed: {message}")]
+    #[diagnostic(code(mekena::system::node_failed))]
+    NodeFailed { node: String, message: String },
+
+    #[error("Node `{node}` missed a deadline: {miss}.")]
+    #[diagnostic(
+        code(mekena::system::deadline_missed),
+        help("Give the node a larger budget or heartbeat interval, or make it do less at a time.")
+    )]
+    DeadlineMissed { node: String, miss: Miss },
+
+    #[error("Could not register the OS signal handlers.")]
+    #[diagnostic(code(mekena::system::signals))]
+    Signals(#[from] std::io::Error),
+
     #[error("An unknown error occured.")]
     #[diagnostic(code(mekena::system::unknown))]
     Unknown,
@@ -0,0 +1,116 @@
+//! Deadline-miss detection. See [`NodeConfig::watchdog`].
+//!
+//! A node can be given a time budget for each iteration, i.e. each call to its
+//! `tick` or `mode_periodic` hook, and a heartbeat interval, within which it
+//! must call [`Context::heartbeat`] while it runs. When either is missed, the
+//! system broadcasts [`NodeEvent::DeadlineMissed`], logs a diagnostic as a
+//! warning through the [`log`] facade, and takes the configured
+//! [`WatchdogAction`]. Watch for the event with [`Context::events`] to react
+//! to misses some other way.
+//!
+//! The budget is enforced while the iteration runs, so an iteration that
+//! hangs is caught as soon as its budget is up.
+//!
+//! [`NodeConfig::watchdog`]: crate::node::NodeConfig::watchdog
+//! [`Context::heartbeat`]: crate::context::Context::heartbeat
+//! [`Context::events`]: crate::context::Context::events
+//! [`NodeEvent::DeadlineMissed`]: crate::event::NodeEvent::DeadlineMissed
+
+use std::{fmt, time::Duration};
+
+use mekena_util::shutdown::ShutdownReason;
+
+use crate::{context::Context, event::NodeEvent, system::SystemError};
+
+/// What to do when a node misses a deadline, on top of reporting it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum WatchdogAction {
+    /// Only report the miss.
+    #[default]
+    Report,
+    /// Pause the node, as with
+    /// [`Context::pause_node`](crate::context::Context::pause_node). Its
+    /// `pause` hook is where it should disable its outputs.
+    DisableOutputs,
+    /// Cancel the node's `running` hook, run its `stopping` and `starting`
+    /// hooks, then run it again.
+    Restart,
+    /// Shut down the whole system, with [`ShutdownReason::Error`].
+    Shutdown,
+}
+
+/// A node's deadlines, and what happens when they are missed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Watchdog {
+    pub(crate) budget: Option<Duration>,
+    pub(crate) heartbeat: Option<Duration>,
+    pub(crate) action: WatchdogAction,
+}
+
+impl Watchdog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The longest a single `tick` or `mode_periodic` call may take.
+    pub fn budget(mut self, budget: Duration) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// The longest the node may go without calling
+    /// [`Context::heartbeat`](crate::context::Context::heartbeat) while it
+    /// runs.
+    pub fn heartbeat(mut self, interval: Duration) -> Self {
+        self.heartbeat = Some(interval);
+        self
+    }
+
+    pub fn action(mut self, action: WatchdogAction) -> Self {
+        self.action = action;
+        self
+    }
+}
+
+/// A deadline a node missed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Miss {
+    /// An iteration ran over its budget.
+    Budget(Duration),
+    /// No heartbeat arrived within the interval.
+    Heartbeat(Duration),
+}
+
+impl fmt::Display for Miss {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Budget(budget) => write!(f, "an iteration ran over its {budget:?} budget"),
+            Self::Heartbeat(interval) => write!(f, "no heartbeat for {interval:?}"),
+        }
+    }
+}
+
+/// Report that the node `ctx` belongs to missed a deadline, and take the
+/// configured action.
+pub(crate) fn expire(ctx: &Context, watchdog: &Watchdog, miss: Miss) {
+    let id = ctx.node_id().expect("only nodes have watchdogs");
+    let registry = ctx.registry();
+
+    registry.event(id, NodeEvent::DeadlineMissed(miss));
+
+    let error = SystemError::DeadlineMissed {
+        node: registry.name(id).unwrap_or_default(),
+        miss,
+    };
+    let reason = ShutdownReason::error(&error);
+    log::warn!("{:?}", miette::Report::new(error));
+
+    match watchdog.action {
+        WatchdogAction::Report => {}
+        WatchdogAction::DisableOutputs => {
+            registry.request_pause(id, true);
+        }
+        WatchdogAction::Restart => registry.request_restart(id),
+        WatchdogAction::Shutdown => ctx.shutdown_manager().shutdown_with(reason),
+    }
+}
@@ -0,0 +1,255 @@
+//! Statically typed groups of nodes, and the messages they exchange. Usually
+//! built with [`system!`](crate::system!).
+//!
+//! A [`Group`] keeps its nodes in a tuple, so their hooks are called directly
+//! rather than through `Box<dyn Node>`. Otherwise its nodes are just like any
+//! other: each has a context of its own, shares the system's mailbox and
+//! state, and shows up in the system's node listings and events. They start
+//! together, are paused and stopped in reverse order, and a node failing stops
+//! the others and fails the group.
+//!
+//! Every node in a group declares the messages it sends and receives by
+//! implementing [`Messages`], which `#[node(sends(..), receives(..))]` does.
+//! `system!` checks at compile time that every message received in a group is
+//! sent by one of its nodes, or declared external with [`Group::external`]. A
+//! message that isn't fails the build with an error about a missing `is_sent`
+//! method on a [`Received`](check::Received) of that message. Nodes added with
+//! `add_node` aren't checked, and don't count as senders.
+
+use std::{
+    any::{type_name, TypeId},
+    marker::PhantomData,
+};
+
+use mekena_messaging::prelude::Message;
+
+use crate::{
+    context::Context,
+    node::{Node, NodeConfig, NodeState},
+    system::{self, SystemError},
+};
+
+pub mod check;
+
+/// The messages a node sends and receives, as tuples of message types.
+/// `#[node]` implements this when given `sends(..)` or `receives(..)`, either
+/// of which defaults to none.
+pub trait Messages {
+    type Sends: MessageSet;
+    type Receives: MessageSet;
+}
+
+/// A tuple of message types.
+pub trait MessageSet {
+    /// The messages as a nested list, e.g. `(A, (B, ()))`, for
+    /// [`check`].
+    type List;
+}
+
+/// A tuple of nodes, which a [`Group`] can be made of.
+pub trait Nodes {
+    /// The nodes as a nested list, for [`check`].
+    type List;
+
+    /// Each node's [`Messages::Sends`] as a nested list, in a nested list,
+    /// for [`check`].
+    type SendLists;
+
+    /// The name and ID of each node's type.
+    fn types() -> Vec<(&'static str, TypeId)>;
+}
+
+/// Nodes whose hooks are called directly, and the messages sent to them from
+/// outside, `E`. See the [module docs](self).
+pub struct Group<T, E = ()> {
+    nodes: T,
+    /// The nodes' contexts, in order. Created on the first start, and kept
+    /// across run cycles.
+    contexts: Vec<Context>,
+    external: PhantomData<fn() -> E>,
+}
+
+impl<T: Nodes> Group<T> {
+    pub fn new(nodes: T) -> Self {
+        Self {
+            nodes,
+            contexts: Vec::new(),
+            external: PhantomData,
+        }
+    }
+
+    /// Declare messages that are sent from outside the group's nodes, e.g.
+    /// through a [`SystemHandle`](crate::handle::SystemHandle).
+    pub fn external<S: MessageSet>(self) -> Group<T, S> {
+        Group {
+            nodes: self.nodes,
+            contexts: self.contexts,
+            external: PhantomData,
+        }
+    }
+}
+
+impl<T: Nodes, E> Group<T, E> {
+    /// Give every node a context scoped under `ctx`, and register it.
+    fn register(&mut self, ctx: &Context) {
+        if self.contexts.is_empty() {
+            self.contexts = T::types().iter().map(|_| ctx.for_node()).collect();
+        }
+
+        let options = NodeConfig::default().options();
+        for (child, (name, type_id)) in self.contexts.iter_mut().zip(T::types()) {
+            child.rescope(ctx.scope());
+
+            // Registering again resets the record, which a new run cycle drops.
+            let id = child.node_id().expect("node contexts always have an ID");
+            ctx.registry()
+                .register(id, name.to_owned(), type_id, options);
+        }
+    }
+}
+
+impl MessageSet for () {
+    type List = ();
+}
+
+/// Implement [`MessageSet`] for a tuple of messages, given the messages with
+/// their nested list.
+macro_rules! message_set {
+    ($($message:ident),+; $list:ty) => {
+        impl<$($message: Message + 'static),+> MessageSet for ($($message,)+) {
+            type List = $list;
+        }
+    };
+}
+
+message_set!(A; (A, ()));
+message_set!(A, B; (A, (B, ())));
+message_set!(A, B, C; (A, (B, (C, ()))));
+message_set!(A, B, C, D; (A, (B, (C, (D, ())))));
+message_set!(A, B, C, D, E; (A, (B, (C, (D, (E, ()))))));
+message_set!(A, B, C, D, E, F; (A, (B, (C, (D, (E, (F, ())))))));
+message_set!(A, B, C, D, E, F, G; (A, (B, (C, (D, (E, (F, (G, ()))))))));
+message_set!(A, B, C, D, E, F, G, H; (A, (B, (C, (D, (E, (F, (G, (H, ())))))))));
+message_set!(A, B, C, D, E, F, G, H, I; (A, (B, (C, (D, (E, (F, (G, (H, (I, ()))))))))));
+message_set!(A, B, C, D, E, F, G, H, I, J; (A, (B, (C, (D, (E, (F, (G, (H, (I, (J, ())))))))))));
+message_set!(A, B, C, D, E, F, G, H, I, J, K; (A, (B, (C, (D, (E, (F, (G, (H, (I, (J, (K, ()))))))))))));
+message_set!(A, B, C, D, E, F, G, H, I, J, K, L; (A, (B, (C, (D, (E, (F, (G, (H, (I, (J, (K, (L, ())))))))))))));
+
+/// Implement [`Nodes`] and [`Node`] for a tuple of nodes, given the nodes with
+/// their indices, then the indices again in reverse, for stopping, then the
+/// nodes' nested list and that of their `Sends`.
+macro_rules! group {
+    ($($node:ident $index:tt),+; $($reverse:tt),+; $list:ty; $sends:ty) => {
+        impl<$($node: Node + Messages + 'static),+> Nodes for ($($node,)+) {
+            type List = $list;
+            type SendLists = $sends;
+
+            fn types() -> Vec<(&'static str, TypeId)> {
+                vec![$((type_name::<$node>(), TypeId::of::<$node>())),+]
+            }
+        }
+
+        #[async_trait::async_trait(?Send)]
+        impl<$($node: Node + Messages + 'static),+, X> Node for Group<($($node,)+), X> {
+            async fn starting(&mut self, ctx: &Context) {
+                self.register(ctx);
+
+                let contexts = &self.contexts;
+                let result = futures::try_join!(
+                    $(system::start_child(&mut self.nodes.$index, &contexts[$index])),+
+                );
+
+                self.fail(ctx, result).await;
+            }
+
+            async fn running(&mut self, ctx: &Context) {
+                let contexts = &self.contexts;
+                let result = futures::try_join!(
+                    $(system::run(&mut self.nodes.$index, &contexts[$index])),+
+                );
+
+                self.fail(ctx, result).await;
+            }
+
+            async fn pause(&mut self, ctx: &Context) {
+                let registry = ctx.registry();
+                let contexts = &self.contexts;
+                let nodes = &mut self.nodes;
+
+                let result = async {
+                    $(
+                        let child = &contexts[$reverse];
+                        let id = child.node_id().expect("node contexts always have an ID");
+                        if let Some(NodeState::Ready | NodeState::Running) = registry.state(id) {
+                            system::pause_node(&mut nodes.$reverse, child).await?;
+                        }
+                    )+
+
+                    Ok(())
+                }
+                .await;
+
+                self.fail(ctx, result).await;
+            }
+
+            async fn resume(&mut self, ctx: &Context) {
+                let registry = ctx.registry();
+                let contexts = &self.contexts;
+                let nodes = &mut self.nodes;
+
+                let result = async {
+                    $(
+                        let child = &contexts[$index];
+                        let id = child.node_id().expect("node contexts always have an ID");
+                        if let Some(NodeState::Paused) = registry.state(id) {
+                            system::resume_node(&mut nodes.$index, child).await?;
+                        }
+                    )+
+
+                    Ok(())
+                }
+                .await;
+
+                self.fail(ctx, result).await;
+            }
+
+            async fn stopping(&mut self, _ctx: &Context) {
+                self.stop_nodes().await;
+            }
+        }
+
+        impl<$($node: Node + Messages + 'static),+, X> Group<($($node,)+), X> {
+            /// Stop the nodes in reverse order, skipping those that failed or
+            /// never finished starting.
+            async fn stop_nodes(&mut self) {
+                $(
+                    if let Some(child) = self.contexts.get($reverse) {
+                        system::stop_child(&mut self.nodes.$reverse, child).await;
+                    }
+                )+
+            }
+
+            /// If a node failed, stop the others, then fail the group with the
+            /// node's error.
+            async fn fail<T>(&mut self, ctx: &Context, result: Result<T, SystemError>) {
+                if let Err(e) = result {
+                    self.stop_nodes().await;
+                    ctx.fail(e);
+                }
+            }
+        }
+    };
+}
+
+group!(A 0; 0; (A, ()); (<A::Sends as MessageSet>::List, ()));
+group!(A 0, B 1; 1, 0; (A, (B, ())); (<A::Sends as MessageSet>::List, (<B::Sends as MessageSet>::List, ())));
+group!(A 0, B 1, C 2; 2, 1, 0; (A, (B, (C, ()))); (<A::Sends as MessageSet>::List, (<B::Sends as MessageSet>::List, (<C::Sends as MessageSet>::List, ()))));
+group!(A 0, B 1, C 2, D 3; 3, 2, 1, 0; (A, (B, (C, (D, ())))); (<A::Sends as MessageSet>::List, (<B::Sends as MessageSet>::List, (<C::Sends as MessageSet>::List, (<D::Sends as MessageSet>::List, ())))));
+group!(A 0, B 1, C 2, D 3, E 4; 4, 3, 2, 1, 0; (A, (B, (C, (D, (E, ()))))); (<A::Sends as MessageSet>::List, (<B::Sends as MessageSet>::List, (<C::Sends as MessageSet>::List, (<D::Sends as MessageSet>::List, (<E::Sends as MessageSet>::List, ()))))));
+group!(A 0, B 1, C 2, D 3, E 4, F 5; 5, 4, 3, 2, 1, 0; (A, (B, (C, (D, (E, (F, ())))))); (<A::Sends as MessageSet>::List, (<B::Sends as MessageSet>::List, (<C::Sends as MessageSet>::List, (<D::Sends as MessageSet>::List, (<E::Sends as MessageSet>::List, (<F::Sends as MessageSet>::List, ())))))));
+group!(A 0, B 1, C 2, D 3, E 4, F 5, G 6; 6, 5, 4, 3, 2, 1, 0; (A, (B, (C, (D, (E, (F, (G, ()))))))); (<A::Sends as MessageSet>::List, (<B::Sends as MessageSet>::List, (<C::Sends as MessageSet>::List, (<D::Sends as MessageSet>::List, (<E::Sends as MessageSet>::List, (<F::Sends as MessageSet>::List, (<G::Sends as MessageSet>::List, ()))))))));
+group!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7; 7, 6, 5, 4, 3, 2, 1, 0; (A, (B, (C, (D, (E, (F, (G, (H, ())))))))); (<A::Sends as MessageSet>::List, (<B::Sends as MessageSet>::List, (<C::Sends as MessageSet>::List, (<D::Sends as MessageSet>::List, (<E::Sends as MessageSet>::List, (<F::Sends as MessageSet>::List, (<G::Sends as MessageSet>::List, (<H::Sends as MessageSet>::List, ())))))))));
+group!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8; 8, 7, 6, 5, 4, 3, 2, 1, 0; (A, (B, (C, (D, (E, (F, (G, (H, (I, ()))))))))); (<A::Sends as MessageSet>::List, (<B::Sends as MessageSet>::List, (<C::Sends as MessageSet>::List, (<D::Sends as MessageSet>::List, (<E::Sends as MessageSet>::List, (<F::Sends as MessageSet>::List, (<G::Sends as MessageSet>::List, (<H::Sends as MessageSet>::List, (<I::Sends as MessageSet>::List, ()))))))))));
+group!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9; 9, 8, 7, 6, 5, 4, 3, 2, 1, 0; (A, (B, (C, (D, (E, (F, (G, (H, (I, (J, ())))))))))); (<A::Sends as MessageSet>::List, (<B::Sends as MessageSet>::List, (<C::Sends as MessageSet>::List, (<D::Sends as MessageSet>::List, (<E::Sends as MessageSet>::List, (<F::Sends as MessageSet>::List, (<G::Sends as MessageSet>::List, (<H::Sends as MessageSet>::List, (<I::Sends as MessageSet>::List, (<J::Sends as MessageSet>::List, ())))))))))));
+group!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10; 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0; (A, (B, (C, (D, (E, (F, (G, (H, (I, (J, (K, ()))))))))))); (<A::Sends as MessageSet>::List, (<B::Sends as MessageSet>::List, (<C::Sends as MessageSet>::List, (<D::Sends as MessageSet>::List, (<E::Sends as MessageSet>::List, (<F::Sends as MessageSet>::List, (<G::Sends as MessageSet>::List, (<H::Sends as MessageSet>::List, (<I::Sends as MessageSet>::List, (<J::Sends as MessageSet>::List, (<K::Sends as MessageSet>::List, ()))))))))))));
+group!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11; 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0; (A, (B, (C, (D, (E, (F, (G, (H, (I, (J, (K, (L, ())))))))))))); (<A::Sends as MessageSet>::List, (<B::Sends as MessageSet>::List, (<C::Sends as MessageSet>::List, (<D::Sends as MessageSet>::List, (<E::Sends as MessageSet>::List, (<F::Sends as MessageSet>::List, (<G::Sends as MessageSet>::List, (<H::Sends as MessageSet>::List, (<I::Sends as MessageSet>::List, (<J::Sends as MessageSet>::List, (<K::Sends as MessageSet>::List, (<L::Sends as MessageSet>::List, ())))))))))))));
@@ -0,0 +1,134 @@
+//! The compile-time check behind [`system!`](crate::system!), that every
+//! message received in a group is sent in it. Not meant to be used directly.
+//!
+//! The senders are the group's external messages and each node's
+//! [`Messages::Sends`], each as a nested list of message types, e.g.
+//! `(A, (B, ()))`. For every message a node receives, `system!` looks for a
+//! sender whose list [`Contains`] it through method resolution:
+//! `(&&..&received::<..>(&group)).is_sent()` has one level per sender, each
+//! implemented one reference further in, so the first sender of the message
+//! provides `is_sent`. A message that no sender sends has no `is_sent` method,
+//! which fails the build with the [`Received`] message in the error.
+//!
+//! Trait bounds alone can't express this: with two nodes sending the same
+//! message, which of them to pick would be ambiguous.
+
+use std::marker::PhantomData;
+
+use super::{Group, MessageSet, Messages, Nodes};
+
+/// Position zero in a nested list.
+pub struct Z;
+
+/// The position after `N`.
+pub struct S<N>(PhantomData<N>);
+
+pub type P0 = Z;
+pub type P1 = S<P0>;
+pub type P2 = S<P1>;
+pub type P3 = S<P2>;
+pub type P4 = S<P3>;
+pub type P5 = S<P4>;
+pub type P6 = S<P5>;
+pub type P7 = S<P6>;
+pub type P8 = S<P7>;
+pub type P9 = S<P8>;
+pub type P10 = S<P9>;
+pub type P11 = S<P10>;
+pub type P12 = S<P11>;
+pub type P13 = S<P12>;
+
+/// Past the end of a nested list, e.g. of the messages a node receives. Every
+/// group sends it.
+pub enum Unused {}
+
+/// The item at position `N` of a nested list, or [`Unused`] past its end.
+pub trait Nth<N> {
+    type Item;
+}
+
+impl<N> Nth<N> for () {
+    type Item = Unused;
+}
+
+impl<H, T> Nth<Z> for (H, T) {
+    type Item = H;
+}
+
+impl<N, H, T: Nth<N>> Nth<S<N>> for (H, T) {
+    type Item = T::Item;
+}
+
+/// Where `M` is found in a nested list.
+pub struct Here;
+
+/// Where `M` is found in the rest of a nested list.
+pub struct There<I>(PhantomData<I>);
+
+/// A nested list that contains `M` at `I`.
+pub trait Contains<M, I> {}
+
+impl<M, T> Contains<M, Here> for (M, T) {}
+
+impl<M, I, H, T: Contains<M, I>> Contains<M, There<I>> for (H, T) {}
+
+/// Every sender of a group, starting with [`Unused`] and the external
+/// messages.
+pub type Senders<T, E> = (
+    (Unused, ()),
+    (<E as MessageSet>::List, <T as Nodes>::SendLists),
+);
+
+/// A message received in a group, to look for among its senders.
+pub struct Received<M, L, I>(PhantomData<fn(M, L, I)>);
+
+/// The message at position `K` of the messages that the node at position `N`
+/// of `group` receives.
+#[allow(clippy::type_complexity)]
+pub fn received<T, E, N, K, I>(
+    _group: &Group<T, E>,
+) -> Received<
+    <<<<T::List as Nth<N>>::Item as Messages>::Receives as MessageSet>::List as Nth<K>>::Item,
+    Senders<T, E>,
+    I,
+>
+where
+    T: Nodes,
+    E: MessageSet,
+    T::List: Nth<N>,
+    <T::List as Nth<N>>::Item: Messages,
+    <<<T::List as Nth<N>>::Item as Messages>::Receives as MessageSet>::List: Nth<K>,
+{
+    Received(PhantomData)
+}
+
+/// Implement a level of the check, for the sender at `$position`, given the
+/// references in front of [`Received`] at that level.
+macro_rules! level {
+    ($level:ident, $position:ty, $($reference:tt)*) => {
+        pub trait $level {
+            fn is_sent(&self) {}
+        }
+
+        impl<M, L: Nth<$position>, I> $level for $($reference)* Received<M, L, I>
+        where
+            L::Item: Contains<M, I>
+        {
+        }
+    };
+}
+
+level!(Level0, P0, & & & & & & & & & & & & &);
+level!(Level1, P1, & & & & & & & & & & & &);
+level!(Level2, P2, & & & & & & & & & & &);
+level!(Level3, P3, & & & & & & & & & &);
+level!(Level4, P4, & & & & & & & & &);
+level!(Level5, P5, & & & & & & & &);
+level!(Level6, P6, & & & & & & &);
+level!(Level7, P7, & & & & & &);
+level!(Level8, P8, & & & & &);
+level!(Level9, P9, & & & &);
+level!(Level10, P10, & & &);
+level!(Level11, P11, & &);
+level!(Level12, P12, &);
+level!(Level13, P13,);